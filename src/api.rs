@@ -1,9 +1,81 @@
+use std::{error::Error, time::Duration};
+
+use reqwest::Client;
+
 use crate::models::*;
 
-const API_STR: &str = "https://api.warframestat.us/pc/";
+/// The public API, which relays fetch from and which is used when the configured relay cannot be reached
+pub const UPSTREAM_URL: &str = "https://api.warframestat.us/";
+/// The platforms the API serves fissures for
+pub const PLATFORMS: &[&str] = &["pc", "ps4", "xb1", "swi"];
+
+/// The fetched fissures and why the configured API was not used for them, if it was not
+pub struct Fetched {
+    pub fissures: Vec<Fissure>,
+    /// The response the fissures were parsed from
+    pub body: String,
+    pub fallback_reason: Option<Box<dyn Error + Send + Sync>>,
+}
+
+/// A client that gives up on unreachable servers quickly, so that falling back does not stall the refresh
+fn client() -> reqwest::Result<Client> {
+    Client::builder()
+        .connect_timeout(Duration::from_secs(5))
+        .timeout(Duration::from_secs(30))
+        .build()
+}
+
+fn fissures_url(api_url: &str, platform: &str) -> String {
+    format!("{}/{}/fissures", api_url.trim_end_matches('/'), platform)
+}
+
+/// Whether the URL is the public API rather than a relay
+pub fn is_upstream(api_url: &str) -> bool {
+    api_url.trim_end_matches('/') == UPSTREAM_URL.trim_end_matches('/')
+}
+
+/// Fetches the fissures from the configured API, falling back to the public API if the configured one is a relay
+/// that cannot be reached or fails
+pub async fn get_fissures(
+    api_url: &str,
+    platform: &str,
+) -> Result<Fetched, Box<dyn Error + Send + Sync>> {
+    match get_fissures_from(api_url, platform).await {
+        Ok((fissures, body)) => Ok(Fetched {
+            fissures,
+            body,
+            fallback_reason: None,
+        }),
+        Err(e) if !is_upstream(api_url) => {
+            let (fissures, body) = get_fissures_from(UPSTREAM_URL, platform).await?;
+            Ok(Fetched {
+                fissures,
+                body,
+                fallback_reason: Some(e),
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Fetches the fissures from the given API.
+/// # Returns
+/// The fissures and the response they were parsed from.
+pub async fn get_fissures_from(
+    api_url: &str,
+    platform: &str,
+) -> Result<(Vec<Fissure>, String), Box<dyn Error + Send + Sync>> {
+    let body = get_fissures_text(api_url, platform).await?;
+    Ok((serde_json::from_str::<Vec<Fissure>>(&body)?, body))
+}
 
-pub async fn get_fissures() -> reqwest::Result<Vec<Fissure>> {
-    let fissures = reqwest::get(&format!("{}{}", API_STR, "fissures"))
-        .await?.json::<Vec<Fissure>>().await?;
-    Ok(fissures)
+/// Fetches the fissures as the API returns them, for relaying them unchanged
+pub async fn get_fissures_text(api_url: &str, platform: &str) -> reqwest::Result<String> {
+    client()?
+        .get(fissures_url(api_url, platform))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await
 }
@@ -1,8 +1,23 @@
-use std::sync::Arc;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use crate::{config::Config, fissure_watcher, models::Fissure};
+use crate::{
+    commands::{self, Command, FilterValue},
+    config::{Config, CONFIG_PATH},
+    filters::FissureFilter,
+    fissure_watcher::{self, Control, FissureEvent},
+    inbox::Inbox,
+    line_editor::{self, LineEditor},
+    notifications::{NotificationAction, NotificationEvent},
+    util::comma_separated_string,
+};
 use ratatui::{text::Text, widgets::*};
-use tokio::sync::{RwLock, mpsc};
+use tokio::sync::RwLock;
+
+/// The window title, preceded by the number of unacknowledged fissures if enabled in the config
+const WINDOW_TITLE: &str = "Reaper's Warframe Tools";
 
 pub struct TabsState<'a> {
     pub titles: Vec<&'a str>,
@@ -10,7 +25,7 @@ pub struct TabsState<'a> {
 }
 
 impl<'a> TabsState<'a> {
-    pub fn new(titles: Vec<&'a str>) -> TabsState {
+    pub fn new(titles: Vec<&'a str>) -> TabsState<'a> {
         TabsState { titles, index: 0 }
     }
     pub fn next(&mut self) {
@@ -33,6 +48,9 @@ pub struct StatefulList<'a> {
 
 impl<'a> StatefulList<'a> {
     pub fn next(&mut self) {
+        if self.list.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i >= self.list.len() - 1 {
@@ -47,6 +65,9 @@ impl<'a> StatefulList<'a> {
     }
 
     pub fn previous(&mut self) {
+        if self.list.is_empty() {
+            return;
+        }
         let i = match self.state.selected() {
             Some(i) => {
                 if i == 0 {
@@ -61,13 +82,28 @@ impl<'a> StatefulList<'a> {
     }
 }
 
+/// The fissures the "Terminal" sink alerted about, shown on top of the current tab
+pub struct Banner {
+    pub summary: String,
+    pub lines: Vec<String>,
+    pub until: Instant,
+}
+
 pub struct App<'a> {
     pub should_quit: bool,
     pub tabs: TabsState<'a>,
     pub console_log: StatefulList<'a>,
-    pub current_cmd: String,
+    pub current_cmd: LineEditor,
     pub config: Arc<RwLock<Config>>,
     pub fissure_watcher: fissure_watcher::FissureWatcher,
+    pub inbox: Inbox,
+    pub banner: Option<Banner>,
+    /// When the tab bar stops flashing
+    pub flash_until: Option<Instant>,
+    /// Whether the terminal bell should be rung
+    bell: bool,
+    window_title: Option<String>,
+    shown_window_title: Option<String>,
 }
 
 impl<'a> App<'a> {
@@ -83,33 +119,305 @@ impl<'a> App<'a> {
         console_log.list.push(text);
         // start fissure watcher
         let fissure_watcher = fissure_watcher::FissureWatcher::new(config.clone());
+        // load command history
+        let current_cmd = LineEditor::with_history(line_editor::load_history().await);
         App {
             should_quit: false,
-            tabs: TabsState::new(vec!["Console", "Fissures", "Settings"]),
+            tabs: TabsState::new(vec![
+                "Console",
+                "Fissures",
+                "Statistics",
+                "Notifications",
+                "Settings",
+            ]),
             console_log,
-            current_cmd: String::new(),
+            current_cmd,
             config,
             fissure_watcher,
+            inbox: Inbox::default(),
+            banner: None,
+            flash_until: None,
+            bell: false,
+            window_title: None,
+            shown_window_title: None,
         }
     }
 
-    fn exec_cmd(&mut self) {
-        match self.current_cmd.as_str() {
-            "quit" => {
+    /// Appends a line to the console log
+    fn log(&mut self, text: impl Into<String>) {
+        self.console_log.list.push(Text::raw(text.into()));
+    }
+
+    async fn exec_cmd(&mut self) {
+        let (input, is_new) = self.current_cmd.submit();
+        if input.is_empty() {
+            return;
+        }
+        if is_new {
+            if let Err(e) = line_editor::append_history(&input).await {
+                self.log(format!("Failed to save command history: {}", e));
+            }
+        }
+        self.log(format!("> {}", input));
+        match input.parse::<Command>() {
+            Ok(command) => self.run_command(command).await,
+            Err(e) => self.log(e),
+        }
+    }
+
+    async fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Help(None) => {
+                for command in commands::COMMANDS {
+                    self.log(format!("{} - {}", command.name, command.description));
+                }
+            }
+            Command::Help(Some(name)) => match commands::find_command(&name) {
+                Some(command) => {
+                    self.log(format!("Usage: {}", command.usage));
+                    self.log(command.description);
+                }
+                None => self.log(format!("Unknown command: \"{}\"", name)),
+            },
+            Command::Quit => {
                 self.should_quit = true;
             }
-            _ => {}
+            Command::Refresh => {
+                self.refilter().await;
+                self.log("Re-applied filters to the current fissures.");
+            }
+            Command::RefreshNow => self.control(Control::RefreshNow).await,
+            Command::Pause => {
+                self.control(Control::Pause).await;
+                self.log("Paused the fissure watcher.");
+            }
+            Command::Resume => {
+                self.control(Control::Resume).await;
+                self.log("Resumed the fissure watcher.");
+            }
+            Command::Interval(seconds) => {
+                self.config.write().await.refresh_rate = seconds;
+                self.control(Control::SetInterval(Duration::from_secs(seconds)))
+                    .await;
+                if self.config.read().await.adaptive_refresh {
+                    self.log(format!(
+                        "Set the refresh interval to {}s, it is used once adaptive refreshing is disabled.",
+                        seconds
+                    ));
+                } else {
+                    self.log(format!("Set the refresh interval to {}s.", seconds));
+                }
+            }
+            Command::Clear => {
+                self.console_log.list.clear();
+                self.console_log.state.select(None);
+            }
+            Command::Tab(name) => {
+                match self
+                    .tabs
+                    .titles
+                    .iter()
+                    .position(|title| title.eq_ignore_ascii_case(&name))
+                {
+                    Some(index) => self.tabs.index = index,
+                    None => self.log(format!(
+                        "Unknown tab: \"{}\", expected one of: {}",
+                        name,
+                        self.tabs.titles.join(", ")
+                    )),
+                }
+            }
+            Command::FilterAdd(value, profile) => {
+                let added = self
+                    .edit_filter(profile.as_deref(), |filter| match value {
+                        FilterValue::Mission(mission) => {
+                            add_unique(&mut filter.mission_filter, mission)
+                        }
+                        FilterValue::Tier(tier) => add_unique(&mut filter.tier_filter, tier),
+                        FilterValue::Faction(faction) => {
+                            add_unique(&mut filter.faction_filter, faction)
+                        }
+                    })
+                    .await;
+                match added {
+                    Ok((true, name)) => {
+                        self.refilter().await;
+                        self.log(format!(
+                            "Added {} to the filters of profile {}.",
+                            value, name
+                        ));
+                    }
+                    Ok((false, name)) => self.log(format!(
+                        "The filters of profile {} already contain {}.",
+                        name, value
+                    )),
+                    Err(e) => self.log(e),
+                }
+            }
+            Command::FilterRemove(value, profile) => {
+                let removed = self
+                    .edit_filter(profile.as_deref(), |filter| match value {
+                        FilterValue::Mission(mission) => {
+                            remove_value(&mut filter.mission_filter, mission)
+                        }
+                        FilterValue::Tier(tier) => remove_value(&mut filter.tier_filter, tier),
+                        FilterValue::Faction(faction) => {
+                            remove_value(&mut filter.faction_filter, faction)
+                        }
+                    })
+                    .await;
+                match removed {
+                    Ok((true, name)) => {
+                        self.refilter().await;
+                        self.log(format!(
+                            "Removed {} from the filters of profile {}.",
+                            value, name
+                        ));
+                    }
+                    Ok((false, name)) => self.log(format!(
+                        "The filters of profile {} do not contain {}.",
+                        name, value
+                    )),
+                    Err(e) => self.log(e),
+                }
+            }
+            Command::FilterStorm(value, profile) => {
+                let set = self
+                    .edit_filter(profile.as_deref(), |filter| {
+                        filter.void_storm_filter = value
+                    })
+                    .await;
+                match set {
+                    Ok(((), name)) => {
+                        self.refilter().await;
+                        self.log(format!(
+                            "Set the void storm filter of profile {} to {}.",
+                            name, value
+                        ));
+                    }
+                    Err(e) => self.log(e),
+                }
+            }
+            Command::FilterList => {
+                let text = self.config.read().await.filters_string();
+                self.log(text);
+            }
+            Command::ConfigShow => {
+                let text = self.config.read().await.to_string();
+                self.log(text);
+            }
+            Command::ConfigReload => {
+                let (config, text) = App::load_config().await;
+                let refresh_rate = config.refresh_rate;
+                *self.config.write().await = config;
+                self.console_log.list.push(text);
+                self.refilter().await;
+                self.control(Control::SetInterval(Duration::from_secs(refresh_rate)))
+                    .await;
+            }
+        }
+    }
+
+    /// Changes the filters of the named profile, the default profile if `None`.
+    /// # Returns
+    /// The result of `edit` and the name of the profile, or an error if there is no such profile.
+    async fn edit_filter<T>(
+        &mut self,
+        profile: Option<&str>,
+        edit: impl FnOnce(&mut FissureFilter) -> T,
+    ) -> Result<(T, String), String> {
+        let mut config = self.config.write().await;
+        let names = config
+            .profiles()
+            .map(|profile| profile.name.clone())
+            .collect::<Vec<String>>();
+        let profile = match profile {
+            None => &mut config.default_profile,
+            Some(name) => config.find_profile_mut(name).ok_or_else(|| {
+                format!(
+                    "Unknown profile: \"{}\", expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })?,
+        };
+        Ok((edit(&mut profile.filter), profile.name.clone()))
+    }
+
+    /// Sends a control message to the fissure watcher, logging any failure
+    async fn control(&mut self, control: Control) {
+        if let Err(e) = self.fissure_watcher.control(control).await {
+            self.log(e);
+        }
+    }
+
+    /// Carries out an action the user clicked on a notification
+    async fn on_notification_action(&mut self, action: NotificationAction) {
+        match action {
+            NotificationAction::Snooze(fissures) => {
+                let minutes = self.config.read().await.snooze_minutes;
+                let ids = fissures.iter().map(|fissure| fissure.id.clone()).collect();
+                self.control(Control::Snooze {
+                    ids,
+                    duration: Duration::from_secs(minutes * 60),
+                })
+                .await;
+                self.log(format!(
+                    "Snoozed {} for {} minutes.",
+                    comma_separated_string(&fissures),
+                    minutes
+                ));
+            }
+            NotificationAction::IgnoreNode(node) => {
+                if add_unique(&mut self.config.write().await.ignored_nodes, node.clone()) {
+                    self.refilter().await;
+                }
+                match Config::save_ignored_node(&node).await {
+                    Ok(()) => self.log(format!(
+                        "Ignoring fissures on {}, added it to ignored_nodes in {}.",
+                        node, CONFIG_PATH
+                    )),
+                    Err(e) => self.log(format!(
+                        "Ignoring fissures on {} until the next restart, saving it to {} failed: {}",
+                        node, CONFIG_PATH, e
+                    )),
+                }
+            }
+            NotificationAction::Open(fissure) => {
+                self.tabs.index = 1;
+                self.fissure_watcher.select(&fissure.id);
+            }
+        }
+    }
+
+    /// Re-applies the current filters to the fissures known by the fissure watcher
+    async fn refilter(&mut self) {
+        let config = self.config.read().await;
+        self.fissure_watcher.refilter(&config);
+    }
+
+    pub(crate) fn on_tab(&mut self) {
+        if self.tabs.index == 0 {
+            let (completed, candidates) =
+                commands::complete(self.current_cmd.text(), &self.tabs.titles);
+            self.current_cmd.set_text(completed);
+            if !candidates.is_empty() {
+                self.log(candidates.join("  "));
+            }
         }
     }
 
     pub(crate) fn on_up(&mut self) {
         match self.tabs.index {
             0 => {
-                self.console_log.previous();
+                self.current_cmd.history_previous();
             }
             1 => {
                 self.fissure_watcher.previous();
             }
+            3 => {
+                self.inbox.previous();
+            }
             _ => {}
         };
     }
@@ -117,34 +425,140 @@ impl<'a> App<'a> {
     pub(crate) fn on_down(&mut self) {
         match self.tabs.index {
             0 => {
-                self.console_log.next();
+                self.current_cmd.history_next();
             }
             1 => {
                 self.fissure_watcher.next();
             }
+            3 => {
+                self.inbox.next();
+            }
             _ => {}
         };
     }
 
+    pub(crate) fn on_page_up(&mut self) {
+        if self.tabs.index == 0 {
+            self.console_log.previous();
+        }
+    }
+
+    pub(crate) fn on_page_down(&mut self) {
+        if self.tabs.index == 0 {
+            self.console_log.next();
+        }
+    }
+
+    /// Whether the arrow keys move the cursor of the console prompt instead of switching tabs
+    fn is_editing(&self) -> bool {
+        self.tabs.index == 0 && !self.current_cmd.is_empty()
+    }
+
     pub(crate) fn on_right(&mut self) {
-        self.tabs.next();
+        if self.is_editing() {
+            self.current_cmd.move_right();
+        } else {
+            self.tabs.next();
+        }
     }
 
     pub(crate) fn on_left(&mut self) {
-        self.tabs.previous();
+        if self.is_editing() {
+            self.current_cmd.move_left();
+        } else {
+            self.tabs.previous();
+        }
+    }
+
+    pub(crate) fn on_home(&mut self) {
+        if self.tabs.index == 0 {
+            self.current_cmd.move_home();
+        }
     }
 
-    pub(crate) fn on_key(&mut self, c: char) {
+    pub(crate) fn on_end(&mut self) {
+        if self.tabs.index == 0 {
+            self.current_cmd.move_end();
+        }
+    }
+
+    pub(crate) async fn on_key(&mut self, c: char) {
         match self.tabs.index {
-            0 => {
-                self.current_cmd.push(c);
-            }
+            0 => self.current_cmd.insert(c),
+            1 => self.on_fissure_key(c).await,
             _ => {}
+        }
+    }
+
+    /// Marks the highlighted fissure in the Fissures tab
+    async fn on_fissure_key(&mut self, c: char) {
+        if c == 'h' {
+            self.fissure_watcher.toggle_show_ignored();
+            return;
+        }
+        let Some(fissure) = self.fissure_watcher.selected().cloned() else {
+            return;
         };
+        match c {
+            'a' => {
+                self.control(Control::Acknowledge(fissure.id.clone())).await;
+                self.log(format!("Acknowledged {}.", fissure));
+            }
+            's' => {
+                let minutes = self.config.read().await.snooze_minutes;
+                self.control(Control::Snooze {
+                    ids: vec![fissure.id.clone()],
+                    duration: Duration::from_secs(minutes * 60),
+                })
+                .await;
+                self.log(format!("Snoozed {} for {} minutes.", fissure, minutes));
+            }
+            'i' => {
+                let ignored = !self
+                    .fissure_watcher
+                    .mark(&fissure.id)
+                    .is_some_and(|mark| mark.ignored);
+                self.control(Control::SetIgnored {
+                    id: fissure.id.clone(),
+                    ignored,
+                })
+                .await;
+                self.log(format!(
+                    "{} {}.",
+                    if ignored {
+                        "Ignoring"
+                    } else {
+                        "No longer ignoring"
+                    },
+                    fissure
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    /// Ctrl+W, deletes the word before the cursor
+    pub(crate) fn on_delete_word(&mut self) {
+        if self.tabs.index == 0 {
+            self.current_cmd.delete_word();
+        }
+    }
+
+    /// Ctrl+U, deletes everything before the cursor
+    pub(crate) fn on_clear_line(&mut self) {
+        if self.tabs.index == 0 {
+            self.current_cmd.clear_before_cursor();
+        }
+    }
+
+    pub(crate) fn on_delete(&mut self) {
+        if self.tabs.index == 0 {
+            self.current_cmd.delete();
+        }
     }
 
     /// The primary tick function for the application.
-    pub(crate) fn update(&mut self) {
+    pub(crate) async fn update(&mut self) {
         // Go through all the events sent from the worker threads
         // Fissure watcher
         while let Ok(event) = self.fissure_watcher.fissure_rx.try_recv() {
@@ -153,13 +567,66 @@ impl<'a> App<'a> {
             let now = time::OffsetDateTime::now_utc();
             let time_stamp = now.format(&time_format).unwrap();
             match event {
-                fissure_watcher::Event::Fissures{fissures, filtered_fissures, new_count} => {
-                    self.fissure_watcher.update_fissures(fissures, filtered_fissures);
-                    self.console_log.list.push(Text::raw(format!("[{}] {} new fissures", time_stamp, new_count)));
+                fissure_watcher::Event::Fissures {
+                    fissures,
+                    filtered_fissures,
+                    events,
+                } => {
+                    self.fissure_watcher
+                        .update_fissures(fissures, filtered_fissures);
+                    let added = events
+                        .iter()
+                        .filter(|event| matches!(event, FissureEvent::Added(_)))
+                        .count();
+                    let removed = events
+                        .iter()
+                        .filter(|event| matches!(event, FissureEvent::Removed { .. }))
+                        .count();
+                    self.log(format!(
+                        "[{}] {} new fissures, {} removed",
+                        time_stamp, added, removed
+                    ));
+                    for event in events {
+                        self.log(format!("[{}]   {}", time_stamp, event));
+                    }
                 }
                 fissure_watcher::Event::Err(e) => {
                     self.console_log.list.push(Text::raw(format!("[{}] Error: {}", time_stamp, e)));
                 }
+                fissure_watcher::Event::Log(message) => {
+                    self.log(format!("[{}] {}", time_stamp, message));
+                }
+                fissure_watcher::Event::Action(action) => {
+                    self.on_notification_action(action).await;
+                }
+                fissure_watcher::Event::Alert(event) => {
+                    self.alert(event).await;
+                }
+                fissure_watcher::Event::Marks(marks) => {
+                    self.fissure_watcher.set_marks(marks);
+                }
+                fissure_watcher::Event::Inbox(entries) => {
+                    self.inbox.set_entries(entries);
+                }
+                fissure_watcher::Event::Notification(entry) => {
+                    self.inbox.push(entry);
+                }
+                fissure_watcher::Event::Reminders(reminders) => {
+                    self.fissure_watcher.reminders = reminders;
+                }
+                fissure_watcher::Event::Statistics(statistics) => {
+                    self.fissure_watcher.statistics = statistics;
+                }
+                fissure_watcher::Event::Prediction(prediction) => {
+                    self.fissure_watcher.prediction = prediction;
+                }
+                fissure_watcher::Event::Schedule {
+                    next_refresh,
+                    adaptive,
+                } => {
+                    self.fissure_watcher.next_refresh = next_refresh;
+                    self.fissure_watcher.adaptive_refresh = adaptive;
+                }
                 fissure_watcher::Event::NoNewFissures => {
                     self.console_log.list.push(Text::raw(format!("[{}] No new fissures", time_stamp)));
                 },
@@ -168,6 +635,62 @@ impl<'a> App<'a> {
         }
         // when the fissures or the filters change we need to update the filtered fissures
 
+        let now = Instant::now();
+        if self
+            .banner
+            .as_ref()
+            .is_some_and(|banner| banner.until <= now)
+        {
+            self.banner = None;
+        }
+        if self.flash_until.is_some_and(|until| until <= now) {
+            self.flash_until = None;
+        }
+        if self.config.read().await.terminal.window_title {
+            self.window_title = Some(match self.fissure_watcher.unacknowledged_count() {
+                0 => WINDOW_TITLE.to_string(),
+                count => format!("({}) {}", count, WINDOW_TITLE),
+            });
+        } else if self.shown_window_title.is_some() {
+            // drop the count when the window title is disabled
+            self.window_title = Some(WINDOW_TITLE.to_string());
+        }
+    }
+
+    /// Rings the bell, flashes the tab bar and shows the banner as configured for the "Terminal" sink
+    async fn alert(&mut self, event: NotificationEvent) {
+        let config = self.config.read().await;
+        let (summary, body) = config
+            .templates
+            .render(&event, time::OffsetDateTime::now_utc());
+        let terminal = config.terminal.clone();
+        drop(config);
+        let now = Instant::now();
+        self.bell |= terminal.bell;
+        if terminal.flash_seconds > 0 {
+            self.flash_until = Some(now + Duration::from_secs(terminal.flash_seconds));
+        }
+        if terminal.banner_seconds > 0 {
+            self.banner = Some(Banner {
+                summary,
+                lines: body.lines().map(str::to_string).collect(),
+                until: now + Duration::from_secs(terminal.banner_seconds),
+            });
+        }
+    }
+
+    /// Whether the terminal bell should be rung, resetting the request
+    pub(crate) fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell)
+    }
+
+    /// The window title, if it changed since it was last taken
+    pub(crate) fn take_window_title(&mut self) -> Option<String> {
+        if self.window_title == self.shown_window_title {
+            return None;
+        }
+        self.shown_window_title = self.window_title.clone();
+        self.window_title.clone()
     }
 
     async fn load_config() -> (Config, Text<'a>) {
@@ -175,10 +698,11 @@ impl<'a> App<'a> {
         match config {
             Ok(config) => (config, Text::raw("Loaded config file.")),
             Err(e) => match e.downcast_ref::<toml::de::Error>() {
-                Some(toml::de::Error { .. }) => {
-                    (Config::default(), Text::raw(
-                            "Error parsing config file, if you have edited it, please fix it, otherwise delete it and restart the program.\nContinuing with default config.",
-                        ))
+                Some(e) => {
+                    (Config::default(), Text::raw(format!(
+                            "Error parsing config file: {}\nIf you have edited it, please fix it, otherwise delete it and restart the program.\nContinuing with default config.",
+                            e
+                        )))
                 }
                 _ => {
                     Config::create_default_file().await.unwrap();
@@ -189,25 +713,54 @@ impl<'a> App<'a> {
     }
 
     pub(crate) fn on_backspace(&mut self) {
-        match self.tabs.index {
-            0 => {
-                self.current_cmd.pop();
-            }
-            _ => {}
-        };
+        if self.tabs.index == 0 {
+            self.current_cmd.backspace();
+        }
     }
 
     pub(crate) fn on_esc(&mut self) {
         self.should_quit = true;
     }
 
-    pub(crate) fn on_enter(&mut self) {
+    pub(crate) async fn on_enter(&mut self) {
         match self.tabs.index {
-            0 => {
-                self.exec_cmd();
-                self.current_cmd.clear();
-            }
+            0 => self.exec_cmd().await,
+            3 => self.open_notification(),
             _ => {}
+        }
+    }
+
+    /// Shows the first fissure of the highlighted notification in the Fissures tab
+    fn open_notification(&mut self) {
+        let Some(fissure) = self
+            .inbox
+            .selected()
+            .and_then(|entry| entry.fissures.first())
+            .cloned()
+        else {
+            return;
         };
+        if self.fissure_watcher.select(&fissure.id) {
+            self.tabs.index = 1;
+        } else {
+            self.log(format!("{} is no longer active.", fissure));
+        }
+    }
+}
+
+/// Pushes `value` onto `list` if it is not already present, returning whether it was added
+fn add_unique<T: PartialEq>(list: &mut Vec<T>, value: T) -> bool {
+    if list.contains(&value) {
+        false
+    } else {
+        list.push(value);
+        true
     }
 }
+
+/// Removes all occurrences of `value` from `list`, returning whether any were removed
+fn remove_value<T: PartialEq>(list: &mut Vec<T>, value: T) -> bool {
+    let len = list.len();
+    list.retain(|v| *v != value);
+    list.len() != len
+}
@@ -0,0 +1,147 @@
+/// Printed for `--help` and when the arguments cannot be parsed
+pub const USAGE: &str = "Usage: reapers-wf [OPTIONS]
+       reapers-wf fissures [--filter-profile <NAME> | --all] [--format <FORMAT>]
+
+Without a command the terminal interface is started.
+
+Commands:
+  fissures  Print the current fissures matching the filters once and exit with status 0 if any are
+            printed, 1 if none are
+
+Options:
+  --headless, daemon       Run the fissure watcher and notifications without the terminal interface,
+                           logging to stdout until SIGTERM or SIGINT
+  --log-format <FORMAT>    Format of the headless log lines: plain or json [default: plain]
+  -h, --help               Print this help
+
+Fissures options:
+  --filter-profile <NAME>  Only print the fissures matching the filters of the profile, \"default\" being the
+                           filters at the top level of the config [default: the filters of all profiles]
+  --all                    Print all fissures, ignoring the filters and ignored nodes
+  --format <FORMAT>        table, json or csv [default: table]";
+
+/// Exit status when no fissure matched
+pub const EXIT_NO_MATCH: i32 = 1;
+/// Exit status when the arguments cannot be parsed
+pub const EXIT_USAGE: i32 = 2;
+/// Exit status when the fissures cannot be fetched, `EX_UNAVAILABLE` from sysexits.h
+pub const EXIT_UNAVAILABLE: i32 = 69;
+/// Exit status when the config cannot be loaded, `EX_CONFIG` from sysexits.h
+pub const EXIT_CONFIG: i32 = 78;
+
+/// Format of the log lines written in headless mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Plain,
+    /// One JSON object per line
+    Json,
+}
+
+/// Format of the fissures printed by the `fissures` command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Table,
+    /// The fissures as returned by the API
+    Json,
+    Csv,
+}
+
+/// Which fissures the `fissures` command prints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selection {
+    /// Those matching any profile and not on an ignored node, like in the terminal interface
+    Matching,
+    /// Those matching the named profile and not on an ignored node
+    Profile(String),
+    All,
+}
+
+/// What the app was started to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mode {
+    Tui,
+    Headless {
+        log_format: LogFormat,
+    },
+    Fissures {
+        selection: Selection,
+        format: OutputFormat,
+    },
+    Help,
+}
+
+/// Parses the command line arguments, without the program name
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Mode, String> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().is_some_and(|arg| arg == "fissures") {
+        args.next();
+        return parse_fissures(args);
+    }
+    let mut headless = false;
+    let mut log_format = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--headless" | "daemon" => headless = true,
+            "--log-format" => {
+                let value = args.next().ok_or("--log-format needs a value")?;
+                log_format = Some(match value.as_str() {
+                    "plain" => LogFormat::Plain,
+                    "json" => LogFormat::Json,
+                    _ => {
+                        return Err(format!(
+                            "Unknown log format \"{}\", expected plain or json",
+                            value
+                        ))
+                    }
+                });
+            }
+            "-h" | "--help" => return Ok(Mode::Help),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+    match (headless, log_format) {
+        (true, log_format) => Ok(Mode::Headless {
+            log_format: log_format.unwrap_or(LogFormat::Plain),
+        }),
+        (false, None) => Ok(Mode::Tui),
+        (false, Some(_)) => Err("--log-format only applies to --headless".to_string()),
+    }
+}
+
+/// Parses the options of the `fissures` command
+fn parse_fissures(mut args: impl Iterator<Item = String>) -> Result<Mode, String> {
+    let mut selection = Selection::Matching;
+    let mut format = OutputFormat::Table;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--filter-profile" if selection == Selection::All => {
+                return Err("--filter-profile cannot be combined with --all".to_string())
+            }
+            "--filter-profile" => {
+                selection =
+                    Selection::Profile(args.next().ok_or("--filter-profile needs a value")?);
+            }
+            "--all" if matches!(selection, Selection::Profile(_)) => {
+                return Err("--all cannot be combined with --filter-profile".to_string())
+            }
+            "--all" => selection = Selection::All,
+            "--format" => {
+                let value = args.next().ok_or("--format needs a value")?;
+                format = match value.as_str() {
+                    "table" => OutputFormat::Table,
+                    "json" => OutputFormat::Json,
+                    "csv" => OutputFormat::Csv,
+                    _ => {
+                        return Err(format!(
+                            "Unknown format \"{}\", expected table, json or csv",
+                            value
+                        ))
+                    }
+                };
+            }
+            "-h" | "--help" => return Ok(Mode::Help),
+            _ => return Err(format!("Unknown argument \"{}\"", arg)),
+        }
+    }
+    Ok(Mode::Fissures { selection, format })
+}
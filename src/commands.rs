@@ -0,0 +1,377 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    filters::{ExclusivityFilter, Factions, Tier},
+    mission_type::MissionType,
+};
+
+/// Static description of a console command, used for help output and tab completion
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+}
+
+/// All commands understood by the console, in the order they are listed by `help`
+pub const COMMANDS: &[CommandSpec] = &[
+    CommandSpec {
+        name: "help",
+        usage: "help [command]",
+        description: "Lists all commands, or shows the usage of the given command",
+    },
+    CommandSpec {
+        name: "quit",
+        usage: "quit",
+        description: "Exits the program",
+    },
+    CommandSpec {
+        name: "refresh",
+        usage: "refresh [now]",
+        description: "Re-applies the filters to the current fissures, or with \"now\" fetches the fissures immediately",
+    },
+    CommandSpec {
+        name: "pause",
+        usage: "pause",
+        description: "Stops fetching fissures until resumed",
+    },
+    CommandSpec {
+        name: "resume",
+        usage: "resume",
+        description: "Resumes fetching fissures",
+    },
+    CommandSpec {
+        name: "interval",
+        usage: "interval <seconds>",
+        description: "Changes the time between refreshes",
+    },
+    CommandSpec {
+        name: "clear",
+        usage: "clear",
+        description: "Clears the console log",
+    },
+    CommandSpec {
+        name: "tab",
+        usage: "tab <console|fissures|statistics|notifications|settings>",
+        description: "Switches to the given tab",
+    },
+    CommandSpec {
+        name: "filter",
+        usage: "filter <add|remove> <mission|tier|faction> <value> [profile] | filter storm <Exclude|Include|Exclusive> [profile] | filter list",
+        description: "Changes the filters of the given profile, by default those at the top level of the config, or lists the active fissure filters",
+    },
+    CommandSpec {
+        name: "config",
+        usage: "config <show|reload>",
+        description: "Shows the current config, or reloads it from the config file",
+    },
+];
+
+/// A parsed console command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Help(Option<String>),
+    Quit,
+    Refresh,
+    RefreshNow,
+    Pause,
+    Resume,
+    Interval(u64),
+    Clear,
+    Tab(String),
+    /// Adds a value to the filters of the named profile, the default profile if `None`
+    FilterAdd(FilterValue, Option<String>),
+    FilterRemove(FilterValue, Option<String>),
+    FilterStorm(ExclusivityFilter, Option<String>),
+    FilterList,
+    ConfigShow,
+    ConfigReload,
+}
+
+/// A single value of one of the list filters in the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterValue {
+    Mission(MissionType),
+    Tier(Tier),
+    Faction(Factions),
+}
+impl Display for FilterValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterValue::Mission(mission) => write!(f, "mission {}", mission),
+            FilterValue::Tier(tier) => write!(f, "tier {}", tier),
+            FilterValue::Faction(faction) => write!(f, "faction {}", faction),
+        }
+    }
+}
+
+const FILTER_ACTIONS: &[&str] = &["add", "remove", "storm", "list"];
+const FILTER_KINDS: &[&str] = &["mission", "tier", "faction"];
+const CONFIG_ACTIONS: &[&str] = &["show", "reload"];
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args = s.split_whitespace().collect::<Vec<&str>>();
+        let unknown = || {
+            format!(
+                "Unknown command: \"{}\". Type \"help\" for a list of commands.",
+                s.trim()
+            )
+        };
+        let usage = |name: &str| {
+            format!(
+                "Invalid arguments: \"{}\". Usage: {}",
+                s.trim(),
+                find_command(name).map(|c| c.usage).unwrap_or_default()
+            )
+        };
+        match args.as_slice() {
+            ["help"] => Ok(Command::Help(None)),
+            ["help", command] => Ok(Command::Help(Some(command.to_string()))),
+            ["quit"] => Ok(Command::Quit),
+            ["refresh"] => Ok(Command::Refresh),
+            ["refresh", "now"] => Ok(Command::RefreshNow),
+            ["pause"] => Ok(Command::Pause),
+            ["resume"] => Ok(Command::Resume),
+            ["interval", seconds] => match seconds.parse::<u64>() {
+                Ok(seconds) if seconds > 0 => Ok(Command::Interval(seconds)),
+                _ => Err(format!("{} is not a valid number of seconds", seconds)),
+            },
+            ["clear"] => Ok(Command::Clear),
+            ["tab", tab] => Ok(Command::Tab(tab.to_string())),
+            ["filter", "list"] => Ok(Command::FilterList),
+            ["filter", "storm", value, profile @ ..] if profile.len() <= 1 => {
+                Ok(Command::FilterStorm(value.parse()?, profile_arg(profile)))
+            }
+            ["filter", "add", kind, value, profile @ ..] if profile.len() <= 1 => Ok(
+                Command::FilterAdd(parse_filter_value(kind, value)?, profile_arg(profile)),
+            ),
+            ["filter", "remove", kind, value, profile @ ..] if profile.len() <= 1 => Ok(
+                Command::FilterRemove(parse_filter_value(kind, value)?, profile_arg(profile)),
+            ),
+            ["config", "show"] => Ok(Command::ConfigShow),
+            ["config", "reload"] => Ok(Command::ConfigReload),
+            [name, ..] if find_command(name).is_some() => Err(usage(name)),
+            _ => Err(unknown()),
+        }
+    }
+}
+
+fn parse_filter_value(kind: &str, value: &str) -> Result<FilterValue, String> {
+    match kind {
+        "mission" => Ok(FilterValue::Mission(value.parse()?)),
+        "tier" => Ok(FilterValue::Tier(value.parse()?)),
+        "faction" => Ok(FilterValue::Faction(value.parse()?)),
+        _ => Err(format!(
+            "{} is not a valid filter, expected one of: {}",
+            kind,
+            FILTER_KINDS.join(", ")
+        )),
+    }
+}
+
+/// The optional profile name following the arguments of a filter command
+fn profile_arg(rest: &[&str]) -> Option<String> {
+    rest.first().map(|profile| profile.to_string())
+}
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// Returns the possible values for the word following the already typed `args`
+fn candidates(args: &[&str], tab_titles: &[&str]) -> Vec<String> {
+    let strings = |values: &[&str]| values.iter().map(|v| v.to_string()).collect();
+    match args {
+        [] => COMMANDS.iter().map(|c| c.name.to_string()).collect(),
+        ["help"] => COMMANDS.iter().map(|c| c.name.to_string()).collect(),
+        ["refresh"] => vec!["now".to_string()],
+        ["tab"] => tab_titles.iter().map(|t| t.to_lowercase()).collect(),
+        ["filter"] => strings(FILTER_ACTIONS),
+        ["filter", "add" | "remove"] => strings(FILTER_KINDS),
+        ["filter", "add" | "remove", "mission"] => strings(MissionType::VARIANT_NAMES),
+        ["filter", "add" | "remove", "tier"] => strings(Tier::VARIANT_NAMES),
+        ["filter", "add" | "remove", "faction"] => strings(Factions::VARIANT_NAMES),
+        ["filter", "storm"] => strings(ExclusivityFilter::VARIANT_NAMES),
+        ["config"] => strings(CONFIG_ACTIONS),
+        _ => Vec::new(),
+    }
+}
+
+/// Completes the last word of `input`.
+/// # Returns
+/// The completed input and, if the completion is ambiguous, the list of matching candidates.
+pub fn complete(input: &str, tab_titles: &[&str]) -> (String, Vec<String>) {
+    let mut args = input.split_whitespace().collect::<Vec<&str>>();
+    // the word being completed is empty if the input ends with a space
+    let partial = if input.is_empty() || input.ends_with(char::is_whitespace) {
+        ""
+    } else {
+        args.pop().unwrap_or_default()
+    };
+    let matches = candidates(&args, tab_titles)
+        .into_iter()
+        .filter(|candidate| {
+            candidate
+                .to_lowercase()
+                .starts_with(&partial.to_lowercase())
+        })
+        .collect::<Vec<String>>();
+    let prefix = if args.is_empty() {
+        String::new()
+    } else {
+        format!("{} ", args.join(" "))
+    };
+    match matches.as_slice() {
+        [] => (input.to_string(), Vec::new()),
+        [single] => (format!("{}{} ", prefix, single), Vec::new()),
+        _ => {
+            let common = common_prefix(&matches);
+            let completed = if common.chars().count() > partial.chars().count() {
+                format!("{}{}", prefix, common)
+            } else {
+                input.to_string()
+            };
+            (completed, matches)
+        }
+    }
+}
+
+fn common_prefix(values: &[String]) -> String {
+    let first = &values[0];
+    let mut len = first.chars().count();
+    for value in &values[1..] {
+        len = first
+            .chars()
+            .zip(value.chars())
+            .take_while(|(a, b)| a == b)
+            .count()
+            .min(len);
+    }
+    first.chars().take(len).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Command, String> {
+        s.parse::<Command>()
+    }
+
+    #[test]
+    fn parses_commands_and_arguments() {
+        assert_eq!(parse("help"), Ok(Command::Help(None)));
+        assert_eq!(
+            parse("  help   filter "),
+            Ok(Command::Help(Some("filter".to_string())))
+        );
+        assert_eq!(parse("refresh now"), Ok(Command::RefreshNow));
+        assert_eq!(parse("interval 30"), Ok(Command::Interval(30)));
+        assert_eq!(
+            parse("tab fissures"),
+            Ok(Command::Tab("fissures".to_string()))
+        );
+        assert_eq!(
+            parse("filter add mission Survival"),
+            Ok(Command::FilterAdd(
+                FilterValue::Mission(MissionType::Survival),
+                None
+            ))
+        );
+        assert_eq!(
+            parse("filter remove tier Axi radshare"),
+            Ok(Command::FilterRemove(
+                FilterValue::Tier(Tier::Axi),
+                Some("radshare".to_string())
+            ))
+        );
+        assert_eq!(
+            parse("filter storm Include radshare"),
+            Ok(Command::FilterStorm(
+                ExclusivityFilter::Include,
+                Some("radshare".to_string())
+            ))
+        );
+        assert_eq!(parse("config reload"), Ok(Command::ConfigReload));
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert_eq!(
+            parse("interval 0"),
+            Err("0 is not a valid number of seconds".to_string())
+        );
+        assert!(parse("interval soon").is_err());
+        assert_eq!(
+            parse("quit now"),
+            Err("Invalid arguments: \"quit now\". Usage: quit".to_string())
+        );
+        assert_eq!(
+            parse("filter add planet Sedna"),
+            Err(
+                "planet is not a valid filter, expected one of: mission, tier, faction".to_string()
+            )
+        );
+        assert!(parse("filter add tier Bronze").is_err());
+        assert!(parse("filter storm").is_err());
+        assert!(parse("filter add tier Axi radshare extra").is_err());
+        assert_eq!(
+            parse("jump"),
+            Err("Unknown command: \"jump\". Type \"help\" for a list of commands.".to_string())
+        );
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn completes_unique_candidates() {
+        assert_eq!(complete("he", &[]), ("help ".to_string(), Vec::new()));
+        assert_eq!(
+            complete("filter a", &[]),
+            ("filter add ".to_string(), Vec::new())
+        );
+        assert_eq!(
+            complete("filter add tier ax", &[]),
+            ("filter add tier Axi ".to_string(), Vec::new())
+        );
+        assert_eq!(
+            complete("tab n", &["Console", "Notifications"]),
+            ("tab notifications ".to_string(), Vec::new())
+        );
+        // nothing to complete
+        assert_eq!(complete("quit ", &[]), ("quit ".to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn completes_ambiguous_candidates_to_their_common_prefix() {
+        assert_eq!(
+            complete("re", &[]),
+            (
+                "re".to_string(),
+                vec!["refresh".to_string(), "resume".to_string()]
+            )
+        );
+        assert_eq!(
+            complete("p", &["Statistics", "Settings"]).0,
+            "pause ".to_string()
+        );
+        assert_eq!(
+            complete("tab s", &["Statistics", "Settings"]),
+            (
+                "tab s".to_string(),
+                vec!["statistics".to_string(), "settings".to_string()]
+            )
+        );
+        let (completed, matches) = complete("", &[]);
+        assert_eq!(completed, "");
+        assert_eq!(matches.len(), COMMANDS.len());
+    }
+
+    #[test]
+    fn common_prefix_counts_chars() {
+        let values = ["Überlauf".to_string(), "Übersicht".to_string()];
+        assert_eq!(common_prefix(&values), "Über");
+        assert_eq!(common_prefix(&values[..1]), "Überlauf");
+    }
+}
@@ -1,27 +1,159 @@
 use std::{error::Error, fmt::Display};
 
+use serde::{Deserialize, Deserializer};
+
 use crate::{
-    filters::{ExclusivityFilter, Factions, Tier},
-    mission_type::MissionType,
+    api,
+    filters::FissureFilter,
     models::Fissure,
+    mqtt::MqttConfig,
+    notifications::{
+        EmailConfig, ExecConfig, PolicyConfig, SinkKind, TerminalConfig, Urgency, WebhookConfig,
+    },
+    server::ServerConfig,
+    template::NotificationTemplates,
     util::comma_separated_string,
 };
 
 const DEFAULT_CONFIG: &str = include_str!("../default-config.toml");
 pub const CONFIG_PATH: &str = "reapers-wf-config.toml";
 
-#[derive(serde::Deserialize)]
+#[derive(Clone, serde::Deserialize)]
 pub struct Config {
-    pub mission_filter: Vec<MissionType>,
-    pub tier_filter: Vec<Tier>,
-    pub faction_filter: Vec<Factions>,
-    /// Whether to include, exclude or exclusively filter for void storms
-    pub void_storm_filter: ExclusivityFilter,
+    /// The filters and notification settings at the top level of the config file
+    #[serde(flatten)]
+    pub default_profile: Profile,
+    /// Additional named filter profiles, a fissure matching any profile is shown and announced
+    #[serde(default)]
+    pub profiles: Vec<Profile>,
+    /// Base URL of the warframestat.us compatible API the fissures are fetched from, e.g. a relay on the LAN
+    #[serde(default = "default_api_url", deserialize_with = "deserialize_api_url")]
+    pub api_url: String,
+    #[serde(
+        default = "default_platform",
+        deserialize_with = "deserialize_platform"
+    )]
+    pub platform: String,
     /// How often to refresh the fissure list in seconds
     pub refresh_rate: u64,
+    /// Whether to time refreshes around the expiry of the known fissures instead of using `refresh_rate`
+    #[serde(default = "default_adaptive_refresh")]
+    pub adaptive_refresh: bool,
+    /// Shortest time between adaptive refreshes in seconds
+    #[serde(default = "default_min_refresh_rate")]
+    pub min_refresh_rate: u64,
+    /// Longest time between adaptive refreshes in seconds
+    #[serde(default = "default_max_refresh_rate")]
+    pub max_refresh_rate: u64,
+    /// How long after a fissure expires to keep refreshing at `min_refresh_rate` in seconds
+    #[serde(default = "default_expiry_window")]
+    pub expiry_window: u64,
     /// How long before the fissure expires to send a notification in seconds
     pub time_before_expiry_notification: u64,
+    /// Whether to announce all matching fissures on startup, including those already announced before the last shutdown
+    #[serde(default)]
+    pub announce_on_startup: bool,
+    /// Where to send notifications, several sinks can be enabled at once
+    #[serde(default = "default_notification_sinks")]
+    pub notification_sinks: Vec<SinkKind>,
+    /// Endpoints the "Webhook" sink sends a request to for every notified fissure
+    #[serde(default)]
+    pub webhooks: Vec<WebhookConfig>,
+    /// The command the "Exec" sink runs for every notified fissure
+    #[serde(default)]
+    pub exec: Option<ExecConfig>,
+    /// The SMTP server and recipients of the "Email" sink
+    #[serde(default)]
+    pub email: Option<EmailConfig>,
+    /// The summary and body of notifications, per kind of event
+    #[serde(default)]
+    pub templates: NotificationTemplates,
+    /// When notifications are sent
+    #[serde(default)]
+    pub notification_policy: PolicyConfig,
+    /// The broker the matching fissures and their events are published to
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    /// The embedded HTTP server serving the fissures and their events
+    #[serde(default)]
+    pub server: Option<ServerConfig>,
+    /// The alerts of the "Terminal" sink and the window title
+    #[serde(default)]
+    pub terminal: TerminalConfig,
+    /// Nodes whose fissures are neither shown nor notified
+    #[serde(default)]
+    pub ignored_nodes: Vec<String>,
+    /// How many minutes a notification is snoozed for
+    #[serde(default = "default_snooze_minutes")]
+    pub snooze_minutes: u64,
+}
+/// A named set of filters with its own notification settings
+#[derive(Clone, serde::Deserialize)]
+pub struct Profile {
+    #[serde(default = "default_profile_name")]
+    pub name: String,
+    #[serde(flatten)]
+    pub filter: FissureFilter,
+    /// Discord webhook URLs that fissures matching this profile are posted to
+    #[serde(default)]
+    pub discord_webhooks: Vec<String>,
+    /// Whether fissures matching this profile are notified during quiet hours
+    #[serde(default)]
+    pub urgent: bool,
+    /// Urgency of the desktop notifications about fissures matching this profile
+    #[serde(default)]
+    pub urgency: Urgency,
+}
+
+fn default_api_url() -> String {
+    api::UPSTREAM_URL.to_string()
+}
+fn default_platform() -> String {
+    "pc".to_string()
+}
+fn deserialize_api_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    match reqwest::Url::parse(&text) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(text),
+        _ => Err(serde::de::Error::custom(format!(
+            "Invalid API URL \"{}\", expected http://host:port/ or https://host/",
+            text
+        ))),
+    }
+}
+fn deserialize_platform<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let platform = String::deserialize(deserializer)?;
+    if !api::PLATFORMS.contains(&platform.as_str()) {
+        return Err(serde::de::Error::custom(format!(
+            "Unknown platform \"{}\", expected one of: {}",
+            platform,
+            api::PLATFORMS.join(", ")
+        )));
+    }
+    Ok(platform)
+}
+fn default_snooze_minutes() -> u64 {
+    10
+}
+fn default_profile_name() -> String {
+    "default".to_string()
+}
+fn default_notification_sinks() -> Vec<SinkKind> {
+    vec![SinkKind::Desktop]
 }
+fn default_adaptive_refresh() -> bool {
+    true
+}
+fn default_min_refresh_rate() -> u64 {
+    30
+}
+fn default_max_refresh_rate() -> u64 {
+    300
+}
+fn default_expiry_window() -> u64 {
+    180
+}
+
 impl Config {
     pub async fn create_default_file() -> Result<(), Box<dyn Error>> {
         tokio::fs::write(CONFIG_PATH, DEFAULT_CONFIG).await?;
@@ -32,71 +164,119 @@ impl Config {
         let conf = toml::from_str::<Config>(&config)?;
         Ok(conf)
     }
-    pub fn apply_filters_cloned<'a>(&self, fissures: &Vec<Fissure>) -> Vec<Fissure> {
+    /// Adds the node to `ignored_nodes` in the config file, keeping the rest of the file as it is
+    pub async fn save_ignored_node(node: &str) -> Result<(), Box<dyn Error>> {
+        let text = tokio::fs::read_to_string(CONFIG_PATH).await?;
+        let text = add_ignored_node(&text, node)?;
+        tokio::fs::write(CONFIG_PATH, text).await?;
+        Ok(())
+    }
+    /// The default profile followed by the additional profiles
+    pub fn profiles(&self) -> impl Iterator<Item = &Profile> {
+        std::iter::once(&self.default_profile).chain(self.profiles.iter())
+    }
+
+    /// The profile with the given name, ignoring case
+    pub fn find_profile(&self, name: &str) -> Option<&Profile> {
+        self.profiles()
+            .find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The profile with the given name, ignoring case, for changing it
+    pub fn find_profile_mut(&mut self, name: &str) -> Option<&mut Profile> {
+        std::iter::once(&mut self.default_profile)
+            .chain(self.profiles.iter_mut())
+            .find(|profile| profile.name.eq_ignore_ascii_case(name))
+    }
+
+    /// All profiles whose filters the given fissure passes, none if its node is ignored
+    pub fn matching_profiles(&self, fissure: &Fissure) -> Vec<&Profile> {
+        if self.ignored_nodes.contains(&fissure.node) {
+            return Vec::new();
+        }
+        self.profiles()
+            .filter(|profile| profile.filter.matches(fissure))
+            .collect()
+    }
+
+    /// Returns a human readable, multi-line description of the active filters
+    pub fn filters_string(&self) -> String {
+        let mut text = self.default_profile.filter.to_string();
+        for profile in &self.profiles {
+            text.push_str(&format!("\nProfile {}:\n{}", profile.name, profile.filter));
+        }
+        text
+    }
+
+    /// Whether the given fissure passes the filters of any profile and is not on an ignored node
+    pub fn matches(&self, fissure: &Fissure) -> bool {
+        !self.ignored_nodes.contains(&fissure.node)
+            && self
+                .profiles()
+                .any(|profile| profile.filter.matches(fissure))
+    }
+
+    pub fn apply_filters_cloned(&self, fissures: &[Fissure]) -> Vec<Fissure> {
         fissures
             .iter()
-            .filter(|fissure| {
-                self.mission_filter
-                    .iter()
-                    .any(|filter| filter.apply_filter(fissure))
-                    && self
-                        .tier_filter
-                        .iter()
-                        .any(|filter| filter.apply_filter(fissure))
-                    && self
-                        .faction_filter
-                        .iter()
-                        .any(|filter| filter.apply_filter(fissure))
-                    && self.void_storm_filter.apply_filter(fissure.is_storm)
-            })
+            .filter(|fissure| self.matches(fissure))
             .cloned()
             .collect::<Vec<Fissure>>()
     }
 
-    pub fn apply_filters<'a>(&self, fissures: &'a Vec<Fissure>) -> Vec<&'a Fissure> {
+    pub fn apply_filters<'a>(&self, fissures: &'a [Fissure]) -> Vec<&'a Fissure> {
         fissures
             .iter()
-            .filter(|fissure| {
-                self.mission_filter
-                    .iter()
-                    .any(|filter| filter.apply_filter(fissure))
-                    && self
-                        .tier_filter
-                        .iter()
-                        .any(|filter| filter.apply_filter(fissure))
-                    && self
-                        .faction_filter
-                        .iter()
-                        .any(|filter| filter.apply_filter(fissure))
-                    && self.void_storm_filter.apply_filter(fissure.is_storm)
-            })
+            .filter(|fissure| self.matches(fissure))
             .collect::<Vec<&'a Fissure>>()
     }
 }
+/// Adds the node to `ignored_nodes` in the text of a config file, if it is not in there yet
+fn add_ignored_node(text: &str, node: &str) -> Result<String, Box<dyn Error>> {
+    let mut document = text.parse::<toml_edit::Document>()?;
+    let nodes = document
+        .entry("ignored_nodes")
+        .or_insert(toml_edit::value(toml_edit::Array::new()))
+        .as_array_mut()
+        .ok_or("ignored_nodes is not an array")?;
+    if !nodes.iter().any(|value| value.as_str() == Some(node)) {
+        // arrays written one value per line, like the one of the default config, get the node on a line of its own
+        let multiline = nodes
+            .trailing()
+            .as_str()
+            .is_some_and(|trailing| trailing.contains('\n'));
+        if multiline {
+            nodes.push_formatted(toml_edit::Value::from(node).decorated("\n    ", ""));
+            nodes.set_trailing_comma(true);
+        } else {
+            nodes.push(node);
+        }
+    }
+    Ok(document.to_string())
+}
+
 impl Display for Config {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         writeln!(f, "Refresh Rate: {}s", self.refresh_rate)?;
         writeln!(
             f,
-            "Time Before Expiry Notification: {}s",
-            self.time_before_expiry_notification
-        )?;
-        writeln!(
-            f,
-            "Tier Filter: {}",
-            comma_separated_string(&self.tier_filter)
+            "Notification Sinks: {}",
+            comma_separated_string(&self.notification_sinks)
         )?;
         writeln!(
             f,
-            "Mission Filter: {}",
-            comma_separated_string(&self.mission_filter)
+            "Adaptive Refresh: {} (min {}s, max {}s, window {}s)",
+            self.adaptive_refresh, self.min_refresh_rate, self.max_refresh_rate, self.expiry_window
         )?;
         writeln!(
             f,
-            "Faction Filter: {}",
-            comma_separated_string(&self.faction_filter)
+            "Time Before Expiry Notification: {}s",
+            self.time_before_expiry_notification
         )?;
-        write!(f, "Void Storm Filter: {}", self.void_storm_filter)?;
+        if !self.ignored_nodes.is_empty() {
+            writeln!(f, "Ignored Nodes: {}", self.ignored_nodes.join(", "))?;
+        }
+        write!(f, "{}", self.filters_string())?;
         Ok(())
     }
 }
@@ -106,3 +286,35 @@ impl Default for Config {
             .expect("Error parsing default config, default should always be valid")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_ignored_nodes_keeping_the_file() {
+        let text = add_ignored_node(DEFAULT_CONFIG, "Hydron (Sedna)").unwrap();
+        let text = add_ignored_node(&text, "Hydron (Sedna)").unwrap();
+        let config = toml::from_str::<Config>(&text).unwrap();
+        assert_eq!(config.ignored_nodes, ["Hydron (Sedna)"]);
+        assert!(text
+            .contains("ignored_nodes = [\n    \"Hydron (Sedna)\",\n    #\"Hydron (Sedna)\",\n]"));
+        assert!(text.starts_with(&DEFAULT_CONFIG[..DEFAULT_CONFIG.find("ignored_nodes").unwrap()]));
+
+        let text = add_ignored_node("refresh_rate = 60\n", "Hydron (Sedna)").unwrap();
+        assert_eq!(
+            text,
+            "refresh_rate = 60\nignored_nodes = [\"Hydron (Sedna)\"]\n"
+        );
+    }
+
+    #[test]
+    fn parse_errors_name_unknown_placeholders() {
+        let Err(e) =
+            toml::from_str::<Config>("[templates.new]\nsummary = \"{nope}\"\nbody = \"\"\n")
+        else {
+            panic!("the unknown placeholder was accepted");
+        };
+        assert!(e.to_string().contains("Unknown placeholder {nope}"), "{e}");
+    }
+}
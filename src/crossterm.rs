@@ -5,9 +5,14 @@ use std::{
 };
 
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind},
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
     execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    style::Print,
+    terminal::{
+        disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+    },
 };
 use ratatui::prelude::*;
 
@@ -41,7 +46,7 @@ pub async fn run(tick_rate: Duration) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-async fn run_app<'a ,B: Backend>(
+async fn run_app<'a, B: Backend + io::Write>(
     terminal: &mut Terminal<B>,
     mut app: App<'a>,
     tick_rate: Duration,
@@ -57,23 +62,39 @@ async fn run_app<'a ,B: Backend>(
         if crossterm::event::poll(timeout)? {
             if let Event::Key(key) = event::read()? {
                 if key.kind == KeyEventKind::Press {
+                    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
                     match key.code {
-                        KeyCode::Char(c) => app.on_key(c),
+                        KeyCode::Char('w') if ctrl => app.on_delete_word(),
+                        KeyCode::Char('u') if ctrl => app.on_clear_line(),
+                        KeyCode::Char(c) => app.on_key(c).await,
                         KeyCode::Left => app.on_left(),
                         KeyCode::Up => app.on_up(),
                         KeyCode::Right => app.on_right(),
                         KeyCode::Down => app.on_down(),
-                        KeyCode::Enter => app.on_enter(),
+                        KeyCode::Enter => app.on_enter().await,
                         KeyCode::Esc => app.on_esc(),
                         KeyCode::Backspace => app.on_backspace(),
+                        KeyCode::Tab => app.on_tab(),
+                        KeyCode::Delete => app.on_delete(),
+                        KeyCode::Home => app.on_home(),
+                        KeyCode::End => app.on_end(),
+                        KeyCode::PageUp => app.on_page_up(),
+                        KeyCode::PageDown => app.on_page_down(),
                         _ => {}
                     }
                 }
             }
         }
         if last_tick.elapsed() >= tick_rate {
-            app.update();
+            app.update().await;
             last_tick = Instant::now();
+            let backend = terminal.backend_mut();
+            if app.take_bell() {
+                execute!(backend, Print('\x07'))?;
+            }
+            if let Some(title) = app.take_window_title() {
+                execute!(backend, SetTitle(title))?;
+            }
         }
         if app.should_quit {
             return Ok(());
@@ -1,11 +1,66 @@
-use procmacros::{Display, FromStr};
+use procmacros::{Display, FromStr, Variants};
 use serde::{Deserialize, Serialize};
 
-use crate::models::Fissure;
+use crate::{mission_type::MissionType, models::Fissure, util::comma_separated_string};
 
+/// A set of filters a fissure has to pass all of
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FissureFilter {
+    pub mission_filter: Vec<MissionType>,
+    pub tier_filter: Vec<Tier>,
+    pub faction_filter: Vec<Factions>,
+    /// Whether to include, exclude or exclusively filter for void storms
+    pub void_storm_filter: ExclusivityFilter,
+}
+impl FissureFilter {
+    pub fn matches(&self, fissure: &Fissure) -> bool {
+        self.matches_values(
+            fissure.mission_type,
+            fissure.tier,
+            fissure.enemy,
+            fissure.is_storm,
+        )
+    }
+
+    /// Whether a fissure with the given properties passes all filters
+    pub fn matches_values(
+        &self,
+        mission_type: MissionType,
+        tier: Tier,
+        enemy: Factions,
+        is_storm: bool,
+    ) -> bool {
+        self.mission_filter.contains(&mission_type)
+            && self.tier_filter.contains(&tier)
+            && self.faction_filter.contains(&enemy)
+            && self.void_storm_filter.apply_filter(is_storm)
+    }
+}
+impl std::fmt::Display for FissureFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Tier Filter: {}",
+            comma_separated_string(&self.tier_filter)
+        )?;
+        writeln!(
+            f,
+            "Mission Filter: {}",
+            comma_separated_string(&self.mission_filter)
+        )?;
+        writeln!(
+            f,
+            "Faction Filter: {}",
+            comma_separated_string(&self.faction_filter)
+        )?;
+        write!(f, "Void Storm Filter: {}", self.void_storm_filter)
+    }
+}
 
 /// Whether to include, exclude or exclusively use the given value
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, FromStr)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, FromStr, Variants,
+)]
 pub enum ExclusivityFilter {
     /// Exclude those with the given value
     Exclude,
@@ -24,7 +79,9 @@ impl ExclusivityFilter {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, FromStr)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, FromStr, Variants,
+)]
 pub enum Factions {
     Orokin,
     Grineer,
@@ -33,13 +90,10 @@ pub enum Factions {
     Narmer,
     Crossfire,
 }
-impl Factions {
-    pub fn apply_filter(&self, value: &Fissure) -> bool {
-        value.enemy == *self
-    }
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, FromStr)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, FromStr, Variants,
+)]
 pub enum Tier {
     Lith,
     Meso,
@@ -47,8 +101,3 @@ pub enum Tier {
     Axi,
     Requiem,
 }
-impl Tier {
-    pub fn apply_filter(&self, value: &Fissure) -> bool {
-        value.tier == *self
-    }
-}
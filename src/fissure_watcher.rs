@@ -1,58 +1,231 @@
 use crate::api;
 use crate::config::Config;
+use crate::history::{History, Statistics};
+use crate::inbox::InboxEntry;
 use crate::models::Fissure;
-use notify_rust::Notification;
-use ratatui::layout::Rect;
+use crate::mqtt::{self, MqttMessage};
+use crate::notifications::{self, NotificationAction, NotificationEvent};
+use crate::prediction::{self, Prediction};
+use crate::reminders::{Reminder, ReminderScheduler, SystemClock};
+use crate::scheduler::PollScheduler;
+use crate::server::{self, Feed, StreamEvent};
+use crate::state::{Mark, State};
+use crate::util::format_duration;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Style, Stylize};
-use ratatui::widgets::{Row, Table, TableState};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph, Row, Table, TableState};
 use ratatui::Frame;
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::sync::Arc;
 use std::time::Duration;
 use time::OffsetDateTime;
-use tokio::sync::mpsc::{self, Sender};
+use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How long before its expiry the reminder of a fissure snoozed past it is due
+const SNOOZE_MARGIN: Duration = Duration::from_secs(60);
 
 pub struct FissureWatcher {
     fissures: Vec<Fissure>,
     filtered_fissures: Vec<Fissure>,
     pub fissure_rx: mpsc::Receiver<Event>,
+    /// Sends control messages to the watcher task
+    pub control_tx: mpsc::Sender<Control>,
     pub fissure_handle: tokio::task::JoinHandle<()>,
+    /// Delivers notifications to the enabled sinks
+    pub notification_handle: tokio::task::JoinHandle<()>,
+    /// Publishes the fissures to the MQTT broker
+    pub mqtt_handle: tokio::task::JoinHandle<()>,
+    /// Serves the fissures and their events over HTTP
+    pub server_handle: tokio::task::JoinHandle<()>,
+    /// When the watcher task will next fetch the fissures, `None` while paused
+    pub next_refresh: Option<OffsetDateTime>,
+    /// Whether the next refresh was timed by the adaptive scheduler
+    pub adaptive_refresh: bool,
+    /// Pending expiry reminders, earliest first
+    pub reminders: Vec<Reminder>,
+    pub statistics: Statistics,
+    pub prediction: Option<Prediction>,
+    /// Marks of fissures by id
+    marks: HashMap<String, Mark>,
+    /// Whether ignored fissures are listed in the table
+    pub show_ignored: bool,
+    /// The filtered fissures listed in the table
+    visible_fissures: Vec<Fissure>,
     table_rows: Vec<Vec<String>>,
     table_state: TableState,
 }
 impl FissureWatcher {
     pub fn new(config: Arc<RwLock<Config>>) -> Self {
         let (fissure_tx, fissure_rx) = mpsc::channel::<Event>(20);
-        let fissure_handle = run(Arc::clone(&config), fissure_tx);
+        let (control_tx, control_rx) = mpsc::channel::<Control>(8);
+        let (notification_tx, notification_handle) =
+            notifications::spawn(Arc::clone(&config), fissure_tx.clone());
+        let (mqtt_tx, mqtt_handle) = mqtt::spawn(Arc::clone(&config), fissure_tx.clone());
+        let (feed, server_handle) = server::spawn(Arc::clone(&config), fissure_tx.clone());
+        let fissure_handle = run(
+            Arc::clone(&config),
+            fissure_tx,
+            control_rx,
+            notification_tx,
+            mqtt_tx,
+            feed,
+        );
         Self {
             fissures: Vec::new(),
             filtered_fissures: Vec::new(),
             fissure_rx,
+            control_tx,
             fissure_handle,
+            notification_handle,
+            mqtt_handle,
+            server_handle,
+            next_refresh: Some(OffsetDateTime::now_utc()),
+            adaptive_refresh: false,
+            reminders: Vec::new(),
+            statistics: Statistics::default(),
+            prediction: None,
+            marks: HashMap::new(),
+            show_ignored: false,
+            visible_fissures: Vec::new(),
             table_rows: Vec::new(),
             table_state: TableState::default(),
         }
     }
 
+    /// Sends a control message to the watcher task
+    pub async fn control(&self, control: Control) -> Result<(), String> {
+        self.control_tx
+            .send(control)
+            .await
+            .map_err(|_| "The fissure watcher is not running".to_string())
+    }
+
     pub fn update_fissures(&mut self, fissures: Vec<Fissure>, filtered_fissures: Vec<Fissure>) {
         self.fissures = fissures;
         self.update_filtered_fissures(filtered_fissures);
     }
 
+    /// Re-applies the filters of the given config to the current fissures
+    pub fn refilter(&mut self, config: &Config) {
+        let filtered_fissures = config.apply_filters_cloned(&self.fissures);
+        self.update_filtered_fissures(filtered_fissures);
+    }
+
     pub fn update_filtered_fissures(&mut self, filtered_fissures: Vec<Fissure>) {
         self.filtered_fissures = filtered_fissures;
-        self.table_rows = self
+        self.update_table();
+    }
+
+    pub fn set_marks(&mut self, marks: HashMap<String, Mark>) {
+        self.marks = marks;
+        self.update_table();
+    }
+
+    pub fn toggle_show_ignored(&mut self) {
+        self.show_ignored = !self.show_ignored;
+        self.update_table();
+    }
+
+    pub fn mark(&self, id: &str) -> Option<&Mark> {
+        self.marks.get(id)
+    }
+
+    /// How many filtered fissures are neither acknowledged nor ignored
+    pub fn unacknowledged_count(&self) -> usize {
+        self.filtered_fissures
+            .iter()
+            .filter(|fissure| !self.mark(&fissure.id).is_some_and(|mark| mark.silenced()))
+            .count()
+    }
+
+    /// How many filtered fissures are ignored
+    pub fn ignored_count(&self) -> usize {
+        self.filtered_fissures
+            .iter()
+            .filter(|fissure| self.mark(&fissure.id).is_some_and(|mark| mark.ignored))
+            .count()
+    }
+
+    /// Rebuilds the table rows, keeping the selected fissure highlighted
+    fn update_table(&mut self) {
+        let selected = self.selected().map(|fissure| fissure.id.clone());
+        self.visible_fissures = self
             .filtered_fissures
             .iter()
-            .map(|fissure| fissure.table_string())
+            .filter(|fissure| {
+                self.show_ignored || !self.mark(&fissure.id).is_some_and(|mark| mark.ignored)
+            })
+            .cloned()
+            .collect();
+        let time_format = time::format_description::parse("[hour]:[minute]").unwrap();
+        self.table_rows = self
+            .visible_fissures
+            .iter()
+            .map(|fissure| {
+                let mut row = fissure.table_string();
+                row.push(match self.marks.get(&fissure.id) {
+                    Some(mark) if mark.ignored => "Ignored".to_string(),
+                    Some(mark) if mark.acknowledged => "Acknowledged".to_string(),
+                    Some(Mark {
+                        snoozed_until: Some(until),
+                        ..
+                    }) => format!("Snoozed until {}", until.format(&time_format).unwrap()),
+                    _ => String::new(),
+                });
+                row
+            })
             .collect::<Vec<Vec<String>>>();
+        match selected {
+            Some(id) => {
+                self.select(&id);
+            }
+            None => self.table_state.select(None),
+        }
+        if self
+            .table_state
+            .selected()
+            .is_some_and(|i| i >= self.visible_fissures.len())
+        {
+            self.table_state
+                .select(self.visible_fissures.len().checked_sub(1));
+        }
+    }
+
+    /// The fissure of the highlighted row
+    pub fn selected(&self) -> Option<&Fissure> {
+        self.table_state
+            .selected()
+            .and_then(|i| self.visible_fissures.get(i))
+    }
+
+    /// Highlights the row of the fissure with the given id, if it is shown
+    /// # Returns
+    /// Whether the fissure is shown.
+    pub fn select(&mut self, id: &str) -> bool {
+        match self
+            .visible_fissures
+            .iter()
+            .position(|fissure| fissure.id == id)
+        {
+            Some(i) => {
+                self.table_state.select(Some(i));
+                true
+            }
+            None => false,
+        }
     }
 
     pub fn next(&mut self) {
+        if self.visible_fissures.is_empty() {
+            return;
+        }
         let i = match self.table_state.selected() {
             Some(i) => {
-                if i >= self.filtered_fissures.len() - 1 {
+                if i >= self.visible_fissures.len() - 1 {
                     0
                 } else {
                     i + 1
@@ -64,10 +237,13 @@ impl FissureWatcher {
     }
 
     pub fn previous(&mut self) {
+        if self.visible_fissures.is_empty() {
+            return;
+        }
         let i = match self.table_state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.filtered_fissures.len() - 1
+                    self.visible_fissures.len() - 1
                 } else {
                     i - 1
                 }
@@ -77,9 +253,84 @@ impl FissureWatcher {
         self.table_state.select(Some(i));
     }
     pub fn draw(&mut self, f: &mut Frame, area: Rect) {
-        let header = Fissure::table_headers();
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Min(0),     // fissures
+                Constraint::Length(48), // pending reminders
+            ])
+            .split(area);
+        let table_chunks = Layout::default()
+            .constraints([
+                Constraint::Length(1), // prediction
+                Constraint::Min(0),    // table
+                Constraint::Length(1), // keys
+            ])
+            .split(chunks[0]);
+        self.draw_prediction(f, table_chunks[0]);
+        self.draw_table(f, table_chunks[1]);
+        self.draw_keys(f, table_chunks[2]);
+        self.draw_reminders(f, chunks[1]);
+    }
+
+    fn draw_prediction(&self, f: &mut Frame, area: Rect) {
+        let text = match self.prediction {
+            Some(prediction) => {
+                let now = OffsetDateTime::now_utc();
+                let until =
+                    |time: OffsetDateTime| format_duration((time - now).max(time::Duration::ZERO));
+                format!(
+                    "Next matching fissure expected in ~{} (80% between {} and {}), based on {} recorded fissures",
+                    until(prediction.expected),
+                    until(prediction.earliest),
+                    prediction
+                        .latest
+                        .map(until)
+                        .unwrap_or_else(|| "over a week".to_string()),
+                    prediction.samples
+                )
+            }
+            None => "Not enough fissure history to estimate the next matching fissure".to_string(),
+        };
+        f.render_widget(Paragraph::new(text).italic(), area);
+    }
+
+    fn draw_keys(&self, f: &mut Frame, area: Rect) {
+        let text = format!(
+            "a: acknowledge  s: snooze  i: ignore/unignore  h: {} ignored ({})",
+            if self.show_ignored { "hide" } else { "show" },
+            self.ignored_count()
+        );
+        f.render_widget(Paragraph::new(text).italic(), area);
+    }
+
+    fn draw_reminders(&self, f: &mut Frame, area: Rect) {
+        let time_format: Vec<time::format_description::FormatItem<'_>> =
+            time::format_description::parse("[hour]:[minute]:[second]").unwrap();
+        let items = self
+            .reminders
+            .iter()
+            .map(|reminder| {
+                ListItem::new(format!(
+                    "{} {}",
+                    reminder.due.format(&time_format).unwrap(),
+                    reminder.fissure
+                ))
+            })
+            .collect::<Vec<ListItem>>();
+        let list = List::new(items).block(
+            Block::default()
+                .borders(Borders::LEFT)
+                .title("Pending reminders"),
+        );
+        f.render_widget(list, area);
+    }
+
+    fn draw_table(&mut self, f: &mut Frame, area: Rect) {
+        let mut header = Fissure::table_headers();
+        header.push("Status".to_string());
         let widths = crate::ui::calculate_table_widths(&header, &self.table_rows);
-        let fissure_table = Table::new(self.table_rows.iter().cloned().map(|row| Row::new(row)))
+        let fissure_table = Table::new(self.table_rows.iter().cloned().map(Row::new))
             .header(Row::new(header))
             .widths(&widths)
             .column_spacing(3)
@@ -87,170 +338,770 @@ impl FissureWatcher {
         f.render_stateful_widget(fissure_table, area, &mut self.table_state);
     }
 }
+impl Drop for FissureWatcher {
+    fn drop(&mut self) {
+        self.fissure_handle.abort();
+        self.notification_handle.abort();
+        self.mqtt_handle.abort();
+        self.server_handle.abort();
+    }
+}
 
 pub enum Event {
     Fissures {
         fissures: Vec<Fissure>,
         filtered_fissures: Vec<Fissure>,
-        new_count: usize,
+        events: Vec<FissureEvent>,
     },
     NoNewFissures,
     Err(String),
+    /// A message for the console log
+    Log(String),
+    /// The user clicked an action of a notification
+    Action(NotificationAction),
+    /// The "Terminal" sink alerts about the event
+    Alert(NotificationEvent),
+    /// The marks of the fissures changed
+    Marks(HashMap<String, Mark>),
+    /// The notifications recorded before the app started
+    Inbox(Vec<InboxEntry>),
+    /// A notification was sent or suppressed
+    Notification(InboxEntry),
+    /// The pending expiry reminders changed
+    Reminders(Vec<Reminder>),
+    /// Statistics about the recorded fissure history
+    Statistics(Statistics),
+    /// Estimate of when the next fissure matching the filters appears, `None` without enough history
+    Prediction(Option<Prediction>),
+    /// The time of the next refresh changed
+    Schedule {
+        /// `None` if the watcher is paused
+        next_refresh: Option<OffsetDateTime>,
+        adaptive: bool,
+    },
+}
+
+/// A change to a single fissure between two refreshes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FissureEvent {
+    Added(Fissure),
+    Removed {
+        fissure: Fissure,
+        reason: RemovalReason,
+    },
+    Changed {
+        before: Fissure,
+        after: Fissure,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// The fissure was removed after its expiry time
+    Expired,
+    /// The fissure disappeared before its expiry time
+    Vanished,
+}
+
+impl FissureEvent {
+    /// The fissure the event is about, after the change for `Changed`
+    pub fn fissure(&self) -> &Fissure {
+        match self {
+            FissureEvent::Added(fissure) => fissure,
+            FissureEvent::Removed { fissure, .. } => fissure,
+            FissureEvent::Changed { after, .. } => after,
+        }
+    }
+}
+
+impl Display for FissureEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FissureEvent::Added(fissure) => write!(f, "+ {}", fissure),
+            FissureEvent::Removed {
+                fissure,
+                reason: RemovalReason::Expired,
+            } => write!(f, "- {} (expired)", fissure),
+            FissureEvent::Removed {
+                fissure,
+                reason: RemovalReason::Vanished,
+            } => write!(f, "- {} (vanished)", fissure),
+            FissureEvent::Changed { before, after } => write!(f, "~ {} -> {}", before, after),
+        }
+    }
+}
+
+/// Messages that control the watcher task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Control {
+    /// Fetch the fissures immediately, the next refresh is scheduled one interval later
+    RefreshNow,
+    /// Stop fetching fissures until resumed
+    Pause,
+    Resume,
+    /// Change the time between refreshes when adaptive refreshing is disabled, counting from the last refresh
+    SetInterval(Duration),
+    /// Postpone the reminders of the fissures with the given ids, scheduling them if necessary
+    Snooze {
+        ids: Vec<String>,
+        duration: Duration,
+    },
+    /// Cancel the reminder of the fissure, the user ran or skipped it
+    Acknowledge(String),
+    /// Hide the fissure and never notify it, or undo that
+    SetIgnored {
+        id: String,
+        ignored: bool,
+    },
+}
+
+/// State owned by the watcher task
+struct Watcher {
+    config: Arc<RwLock<Config>>,
+    sender: Sender<Event>,
+    /// The current fissures by id
+    known: HashMap<String, Fissure>,
+    reminders: ReminderScheduler,
+    state: State,
+    history: History,
+    notifier: Sender<NotificationEvent>,
+    mqtt: Sender<MqttMessage>,
+    feed: Feed,
 }
 
-pub fn run(config: Arc<RwLock<Config>>, tx: Sender<Event>) -> JoinHandle<()> {
+pub fn run(
+    config: Arc<RwLock<Config>>,
+    tx: Sender<Event>,
+    mut control_rx: Receiver<Control>,
+    notifier: Sender<NotificationEvent>,
+    mqtt: Sender<MqttMessage>,
+    feed: Feed,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
-        let sender = tx;
-        let mut interval =
-            tokio::time::interval(Duration::from_secs(config.read().await.refresh_rate));
-        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
-        let mut fissures = Vec::new();
+        let (mut scheduler, reminders, announce_on_startup) = {
+            let config = config.read().await;
+            (
+                PollScheduler::new(&config, Duration::from_secs(config.refresh_rate)),
+                ReminderScheduler::new(
+                    SystemClock,
+                    Duration::from_secs(config.time_before_expiry_notification),
+                ),
+                config.announce_on_startup,
+            )
+        };
+        // load the fissures announced before the last shutdown and the marks, announcing everything again if asked to
+        let mut state = match State::load().await.map_err(|e| e.to_string()) {
+            Ok(state) => state,
+            Err(e) => {
+                tx.send(Event::Err(format!("Failed to load state file: {}", e)))
+                    .await
+                    .unwrap();
+                State::default()
+            }
+        };
+        if announce_on_startup {
+            state.clear_seen();
+        }
+        let history = match History::load().await.map_err(|e| e.to_string()) {
+            Ok((history, 0)) => history,
+            Ok((history, skipped)) => {
+                tx.send(Event::Err(format!(
+                    "Skipped {} unreadable lines in the fissure history file",
+                    skipped
+                )))
+                .await
+                .unwrap();
+                history
+            }
+            Err(e) => {
+                tx.send(Event::Err(format!("Failed to load fissure history: {}", e)))
+                    .await
+                    .unwrap();
+                History::default()
+            }
+        };
+        let mut watcher = Watcher {
+            config,
+            sender: tx,
+            known: HashMap::new(),
+            reminders,
+            state,
+            history,
+            notifier,
+            mqtt,
+            feed,
+        };
+        watcher.send_statistics().await;
+        watcher.send_marks().await;
+        let mut paused = false;
+        let mut last_refresh = Instant::now();
+        let mut next_refresh = last_refresh;
         loop {
-            interval.tick().await;
-            // check for new fissures
-            if let Ok((new_count, removed_count)) = update_fissures(&mut fissures).await {
-                if new_count > 0 || removed_count > 0 {
-                    if new_count > 0 {
-                        // apply filters to new fissures
-                        let new_fissures =
-                            &fissures[(fissures.len() - new_count)..(fissures.len())].to_vec();
-                        let filtered_fissures = config.read().await.apply_filters(new_fissures); // WARN: unnecessary clone?
-                                                                                                 // send notification
-                        if filtered_fissures.len() > 0 {
-                            spawn_notifications(
-                                &filtered_fissures,
-                                config.read().await.time_before_expiry_notification,
-                            )
-                            .await;
+            let mut refresh = false;
+            let next_reminder = watcher.reminders.next_due().map(|due| {
+                Instant::now()
+                    + Duration::try_from(due - watcher.reminders.now()).unwrap_or_default()
+            });
+            let reminder_sleep =
+                tokio::time::sleep_until(next_reminder.unwrap_or_else(Instant::now));
+            tokio::select! {
+                _ = tokio::time::sleep_until(next_refresh), if !paused => refresh = true,
+                _ = reminder_sleep, if next_reminder.is_some() => {
+                    watcher.send_due_reminders().await;
+                    continue;
+                }
+                control = control_rx.recv() => match control {
+                    Some(Control::RefreshNow) => refresh = true,
+                    Some(Control::Pause) => paused = true,
+                    Some(Control::Resume) => {
+                        paused = false;
+                        // refresh immediately if the scheduled refresh was missed while paused
+                        next_refresh = next_refresh.max(Instant::now());
+                    }
+                    Some(Control::SetInterval(interval)) => {
+                        scheduler.interval = interval;
+                        if !scheduler.adaptive {
+                            next_refresh = (last_refresh + interval).max(Instant::now());
                         }
                     }
-                    let filtered_fissures = config.read().await.apply_filters_cloned(&fissures);
-                    sender
-                        .send(Event::Fissures {
-                            fissures: fissures.clone(),
-                            filtered_fissures,
-                            new_count,
-                        })
+                    Some(Control::Snooze { ids, duration }) => {
+                        watcher.snooze(&ids, duration).await;
+                        continue;
+                    }
+                    Some(Control::Acknowledge(id)) => {
+                        watcher.acknowledge(&id).await;
+                        continue;
+                    }
+                    Some(Control::SetIgnored { id, ignored }) => {
+                        watcher.set_ignored(&id, ignored).await;
+                        continue;
+                    }
+                    // the app has been dropped
+                    None => return,
+                },
+            }
+            if refresh {
+                let fissures = watcher.poll().await;
+                last_refresh = Instant::now();
+                let now = OffsetDateTime::now_utc();
+                scheduler.configure(&*watcher.config.read().await);
+                scheduler.observe(now, &fissures);
+                next_refresh = last_refresh + scheduler.next_delay(now);
+            }
+            let next = (!paused).then(|| {
+                OffsetDateTime::now_utc() + next_refresh.saturating_duration_since(Instant::now())
+            });
+            watcher
+                .sender
+                .send(Event::Schedule {
+                    next_refresh: next,
+                    adaptive: scheduler.adaptive,
+                })
+                .await
+                .unwrap();
+        }
+    })
+}
+
+impl Watcher {
+    /// Sends statistics about the fissure history and the prediction of the next match, based on the current filters
+    async fn send_statistics(&self) {
+        let (statistics, prediction) = {
+            let config = self.config.read().await;
+            let fissures = self.known.values().cloned().collect::<Vec<Fissure>>();
+            (
+                Statistics::new(self.history.records(), &config),
+                prediction::predict(
+                    self.history.records(),
+                    &fissures,
+                    &config,
+                    OffsetDateTime::now_utc(),
+                ),
+            )
+        };
+        self.sender
+            .send(Event::Statistics(statistics))
+            .await
+            .unwrap();
+        self.sender
+            .send(Event::Prediction(prediction))
+            .await
+            .unwrap();
+    }
+
+    /// Moves the reminders of the given fissures to `duration` from now, or shortly before they expire if that is
+    /// earlier
+    async fn snooze(&mut self, ids: &[String], duration: Duration) {
+        let now = self.reminders.now();
+        for id in ids {
+            match self.known.get(id) {
+                Some(fissure) if fissure.expiry > now => {
+                    let due = snooze_due(fissure, now, duration);
+                    self.reminders.reschedule(fissure.clone(), due);
+                    self.state.update_mark(fissure, |mark| {
+                        mark.acknowledged = false;
+                        mark.snoozed_until = Some(due);
+                    });
+                }
+                _ => {}
+            }
+        }
+        self.marks_changed().await;
+    }
+
+    async fn acknowledge(&mut self, id: &str) {
+        let Some(fissure) = self.known.get(id) else {
+            return;
+        };
+        self.reminders.cancel(id);
+        self.state.update_mark(fissure, |mark| {
+            mark.acknowledged = true;
+            mark.snoozed_until = None;
+        });
+        self.marks_changed().await;
+    }
+
+    async fn set_ignored(&mut self, id: &str, ignored: bool) {
+        let Some(fissure) = self.known.get(id) else {
+            return;
+        };
+        self.state
+            .update_mark(fissure, |mark| mark.ignored = ignored);
+        if ignored {
+            self.reminders.cancel(id);
+        } else if self.config.read().await.matches(fissure) {
+            schedule_reminder(&mut self.reminders, &self.state, fissure);
+        }
+        self.marks_changed().await;
+    }
+
+    /// Saves the state and reports the marks and reminders to the app
+    async fn marks_changed(&mut self) {
+        self.save_state().await;
+        self.publish_fissures().await;
+        self.send_marks().await;
+        self.sender
+            .send(Event::Reminders(self.reminders.pending()))
+            .await
+            .unwrap();
+    }
+
+    async fn send_marks(&self) {
+        self.sender
+            .send(Event::Marks(self.state.marks().clone()))
+            .await
+            .unwrap();
+    }
+
+    async fn save_state(&mut self) {
+        if let Err(e) = self.state.save().await.map_err(|e| e.to_string()) {
+            self.sender
+                .send(Event::Err(format!("Failed to save state file: {}", e)))
+                .await
+                .unwrap();
+        }
+    }
+
+    /// Hands a notification event to the notification task
+    async fn notify(&self, event: NotificationEvent) {
+        self.notifier.send(event).await.unwrap();
+    }
+
+    /// Hands a message to the MQTT task
+    async fn publish(&self, message: MqttMessage) {
+        self.mqtt.send(message).await.unwrap();
+    }
+
+    /// Publishes the fissures matching the filters that are not ignored
+    async fn publish_fissures(&self) {
+        let mut fissures = {
+            let config = self.config.read().await;
+            self.known
+                .values()
+                .filter(|fissure| config.matches(fissure))
+                .filter(|fissure| {
+                    !self
+                        .state
+                        .mark(&fissure.id)
+                        .is_some_and(|mark| mark.ignored)
+                })
+                .cloned()
+                .collect::<Vec<Fissure>>()
+        };
+        fissures.sort_by_key(|fissure| (fissure.tier_num, fissure.expiry));
+        self.publish(MqttMessage::Fissures(fissures)).await;
+    }
+
+    /// Sends the expiry notification of every reminder that is due
+    async fn send_due_reminders(&mut self) {
+        let lead_time = self.config.read().await.time_before_expiry_notification;
+        for reminder in self.reminders.pop_due() {
+            self.feed
+                .send(StreamEvent::expiring(reminder.fissure.clone()));
+            self.publish(MqttMessage::Event {
+                kind: "expiring",
+                fissure: reminder.fissure.clone(),
+            })
+            .await;
+            self.notify(NotificationEvent::Expiring {
+                fissure: reminder.fissure,
+                seconds: lead_time,
+            })
+            .await;
+        }
+        self.sender
+            .send(Event::Reminders(self.reminders.pending()))
+            .await
+            .unwrap();
+    }
+
+    /// Fetches the fissures, sends notifications for new ones matching the filters, updates their expiry reminders
+    /// and reports the result to the app.
+    /// # Returns
+    /// The current fissures, sorted by tier and expiry.
+    async fn poll(&mut self) -> Vec<Fissure> {
+        // check for new fissures
+        let (api_url, platform) = {
+            let config = self.config.read().await;
+            (config.api_url.clone(), config.platform.clone())
+        };
+        let events = match api::get_fissures(&api_url, &platform).await {
+            Ok(fetched) => {
+                if let Some(e) = fetched.fallback_reason {
+                    self.sender
+                        .send(Event::Err(format!(
+                            "Failed to fetch fissures from {}, fetched them from {} instead: {}",
+                            api_url,
+                            api::UPSTREAM_URL,
+                            e
+                        )))
                         .await
                         .unwrap();
-                } else {
-                    sender.send(Event::NoNewFissures).await.unwrap();
                 }
-            } else {
-                sender
+                // saves the relay fetching the same fissures again for its clients
+                self.feed.relay_fetched(&platform, fetched.body);
+                diff_fissures(&mut self.known, fetched.fissures, OffsetDateTime::now_utc())
+            }
+            Err(_) => {
+                self.sender
                     .send(Event::Err("Failed to fetch fissures".to_string()))
                     .await
                     .unwrap();
+                return self.known.values().cloned().collect();
+            }
+        };
+        let mut fissures = self.known.values().cloned().collect::<Vec<Fissure>>();
+        fissures.sort_by_key(|fissure| (fissure.tier_num, fissure.expiry));
+        self.feed.set_fissures(fissures.clone());
+        let config = self.config.read().await;
+        // published once the config is released, as the MQTT task reads it too
+        let mut published = Vec::new();
+        // drop reminders of fissures that no longer match the filters, and follow changes to the lead time
+        self.reminders.retain(|fissure| config.matches(fissure));
+        let state = &self.state;
+        self.reminders.set_lead_time(
+            Duration::from_secs(config.time_before_expiry_notification),
+            |id| state.mark(id).and_then(|mark| mark.snoozed_until),
+        );
+        if events.is_empty() {
+            self.sender.send(Event::NoNewFissures).await.unwrap();
+        } else {
+            let mut new_fissures = Vec::new();
+            for event in &events {
+                self.feed
+                    .send(StreamEvent::new(event, config.matches(event.fissure())));
+                match event {
+                    FissureEvent::Added(fissure) => new_fissures.push(fissure.clone()),
+                    FissureEvent::Removed { fissure, .. } => {
+                        self.reminders.cancel(&fissure.id);
+                        let ignored = self
+                            .state
+                            .mark(&fissure.id)
+                            .is_some_and(|mark| mark.ignored);
+                        if config.matches(fissure) && !ignored {
+                            published.push(MqttMessage::Event {
+                                kind: "removed",
+                                fissure: fissure.clone(),
+                            });
+                        }
+                    }
+                    FissureEvent::Changed { after, .. } => {
+                        // move the pending reminder to the new expiry
+                        if self.reminders.cancel(&after.id) {
+                            schedule_reminder(&mut self.reminders, &self.state, after);
+                        }
+                    }
+                }
+            }
+            // apply filters to new fissures
+            let filtered_fissures = config.apply_filters(&new_fissures);
+            // only announce fissures that were not already announced before a restart,
+            // but restore the reminders of all of them
+            let unseen = filtered_fissures
+                .iter()
+                .copied()
+                .filter(|fissure| !self.state.is_seen(fissure))
+                .filter(|fissure| {
+                    !self
+                        .state
+                        .mark(&fissure.id)
+                        .is_some_and(|mark| mark.ignored)
+                })
+                .collect::<Vec<&Fissure>>();
+            // send notification
+            if !unseen.is_empty() {
+                let unseen = unseen.into_iter().cloned().collect::<Vec<Fissure>>();
+                published.extend(unseen.iter().map(|fissure| MqttMessage::Event {
+                    kind: "new",
+                    fissure: fissure.clone(),
+                }));
+                self.notify(NotificationEvent::NewFissures(unseen)).await;
             }
+            for fissure in filtered_fissures {
+                self.state.mark_seen(fissure);
+                schedule_reminder(&mut self.reminders, &self.state, fissure);
+            }
+            let filtered_fissures = config.apply_filters_cloned(&fissures);
+            self.sender
+                .send(Event::Fissures {
+                    fissures: fissures.clone(),
+                    filtered_fissures,
+                    events: events.clone(),
+                })
+                .await
+                .unwrap();
         }
-    })
-}
-
-/// Consumes a vector of Fissures with the current Fissures, returning None if nothing changed or an updated vector and a count of the new Fissures if something did change
-pub async fn update_fissures(old: &mut Vec<Fissure>) -> reqwest::Result<(usize, usize)> {
-    let current = api::get_fissures().await?;
-    // remove expired fissures
-    let expired: Vec<usize> = old
-        .iter()
-        .enumerate()
-        .filter(|(_, fissure)| current.iter().find(|f| f.id == fissure.id).is_none())
-        .map(|(i, _)| i)
-        .collect();
-    let mut removed_count = 0;
-    for i in expired.into_iter().rev() {
-        old.remove(i);
-        removed_count += 1;
-    }
-    // add new fissures
-    let mut new_count = 0;
-    for fissure in current {
-        if old
+        drop(config);
+        for message in published {
+            self.publish(message).await;
+        }
+        self.publish_fissures().await;
+        // record all new fissures in the history
+        let new_fissures = events
             .iter()
-            .find(|old_fissure| old_fissure.id == fissure.id)
-            .is_none()
+            .filter_map(|event| match event {
+                FissureEvent::Added(fissure) => Some(fissure),
+                _ => None,
+            })
+            .collect::<Vec<&Fissure>>();
+        if let Err(e) = self
+            .history
+            .record(&new_fissures)
+            .await
+            .map_err(|e| e.to_string())
         {
-            old.push(fissure);
-            new_count += 1;
+            self.sender
+                .send(Event::Err(format!(
+                    "Failed to record fissure history: {}",
+                    e
+                )))
+                .await
+                .unwrap();
         }
+        self.send_statistics().await;
+        self.state.prune(OffsetDateTime::now_utc());
+        self.save_state().await;
+        self.sender
+            .send(Event::Reminders(self.reminders.pending()))
+            .await
+            .unwrap();
+        fissures
     }
-    Ok((new_count, removed_count))
 }
 
-/// Runs the fissure watcher, returning a Vec of the filtered Fissures and a count of how many are new and
-/// Sends a notification of any new Fissures and enqueues a notification for each Fissure's expiry once there are the configured amount of seconds left.
-// pub async fn update_filter_notify<'a>(
-//     config: &Config,
-//     fissures: &'a mut Vec<Fissure>,
-// ) -> Result<(Option<Vec<&'a Fissure>>, usize), Box<dyn Error>> {
-//     let (filtered, new_count) = update_and_filter_fissures(config, fissures).await?;
-//     if let Some(filtered_fissures) = filtered {
-//         spawn_notifications(&filtered_fissures, config.time_before_expiry_notification).await;
-//         Ok((Some(filtered_fissures), new_count))
-//     } else {
-//         Ok((None, new_count))
-//     }
-// }
-
-/// Updates the given vector of Fissures with the current Fissures, returning a Vec of the filtered Fissures and a count of how many are new
-// pub async fn update_and_filter_fissures<'a>(
-//     config: &Config,
-//     fissures: &'a mut Vec<Fissure>,
-// ) -> Result<(Option<Vec<&'a Fissure>>, usize), Box<dyn Error>> {
-//     let new_count = update_fissures(fissures).await?;
-//     if new_count == 0 {
-//         Ok((None, 0))
-//     } else {
-//         let filtered_fissures = config.apply_filters(fissures);
-//         if filtered_fissures.len() == 0 {
-//             Ok((None, new_count))
-//         } else {
-//             Ok((Some(filtered_fissures), new_count))
-//         }
-//     }
-// }
-
-/// Sends a notification with the details of each Fissure in the given vector, and enqueues a notification for each Fissure's expiry once there are `time_before_expiry_notification` seconds left.
-pub async fn spawn_notifications(fissures: &Vec<&Fissure>, time_before_expiry_notification: u64) {
-    // send notification
-    Notification::new()
-        .summary("New Fissures")
-        .body(
-            fissures
-                .iter()
-                .map(|fissure| fissure.to_string())
-                .collect::<Vec<String>>()
-                .join("\n")
-                .as_str(),
-        )
-        .show()
-        .unwrap();
-    // enqueue notification for expiry
-    for fissure in fissures {
-        spawn_expiry_notification(fissure, time_before_expiry_notification);
+/// Schedules the expiry reminder of the fissure, unless it is acknowledged or ignored, honouring its snooze
+fn schedule_reminder(reminders: &mut ReminderScheduler, state: &State, fissure: &Fissure) {
+    match state.mark(&fissure.id) {
+        Some(mark) if mark.silenced() => {
+            reminders.cancel(&fissure.id);
+        }
+        Some(Mark {
+            snoozed_until: Some(until),
+            ..
+        }) if *until > reminders.now() && *until < fissure.expiry => {
+            reminders.reschedule(fissure.clone(), *until);
+        }
+        _ => {
+            reminders.schedule(fissure);
+        }
     }
 }
 
-/// Spawns a new tokio task that sends a notification of the given Fissure's expiry once there are `time_before_expiry_notification` seconds left before it expires.
-fn spawn_expiry_notification(fissure: &Fissure, time_before_expiry_notification: u64) {
-    let expiry = fissure.expiry - Duration::from_secs(time_before_expiry_notification);
-    let now = OffsetDateTime::now_utc();
-    if expiry > now {
-        let duration = expiry - now;
-        let sleep_duration = Duration::from_secs_f64(duration.as_seconds_f64());
-        let fissure_str = fissure.to_string();
-        tokio::spawn(async move {
-            tokio::time::sleep(sleep_duration).await;
-            Notification::new()
-                .summary(
-                    format!(
-                        "Fissure is Expiring In {} Seconds",
-                        time_before_expiry_notification
-                    )
-                    .as_str(),
-                )
-                .body(fissure_str.as_str())
-                .show()
-                .unwrap();
-        });
+/// When the reminder of a snoozed fissure is due, no later than `SNOOZE_MARGIN` before it expires
+fn snooze_due(fissure: &Fissure, now: OffsetDateTime, duration: Duration) -> OffsetDateTime {
+    (now + duration)
+        .min(fissure.expiry - SNOOZE_MARGIN)
+        .max(now)
+}
+
+/// Replaces the known Fissures with the current ones, returning the differences between them
+pub fn diff_fissures(
+    known: &mut HashMap<String, Fissure>,
+    current: Vec<Fissure>,
+    now: OffsetDateTime,
+) -> Vec<FissureEvent> {
+    let mut current = current
+        .into_iter()
+        .map(|fissure| (fissure.id.clone(), fissure))
+        .collect::<HashMap<String, Fissure>>();
+    let mut events = Vec::new();
+    // removed fissures
+    for (id, fissure) in known.iter() {
+        if !current.contains_key(id) {
+            let reason = if fissure.expired || fissure.expiry <= now {
+                RemovalReason::Expired
+            } else {
+                RemovalReason::Vanished
+            };
+            events.push(FissureEvent::Removed {
+                fissure: fissure.clone(),
+                reason,
+            });
+        }
+    }
+    // new and changed fissures
+    for (id, fissure) in current.iter() {
+        match known.get(id) {
+            None => events.push(FissureEvent::Added(fissure.clone())),
+            Some(before) if before.has_changed(fissure) => events.push(FissureEvent::Changed {
+                before: before.clone(),
+                after: fissure.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    std::mem::swap(known, &mut current);
+    events.sort_by_key(|event| {
+        let order = match event {
+            FissureEvent::Removed { .. } => 0,
+            FissureEvent::Changed { .. } => 1,
+            FissureEvent::Added(_) => 2,
+        };
+        let fissure = event.fissure();
+        (order, fissure.tier_num, fissure.expiry)
+    });
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filters::Tier;
+
+    /// 2024-01-01 12:00 UTC
+    fn now() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap()
+    }
+
+    fn fissure(id: &str, tier: Tier, minutes_left: i64) -> Fissure {
+        let mut fissure = Fissure::sample(
+            id,
+            "Hydron (Sedna)",
+            now() + time::Duration::minutes(minutes_left),
+        );
+        fissure.tier = tier;
+        fissure.tier_num = Tier::VARIANTS.iter().position(|t| *t == tier).unwrap() as u8 + 1;
+        fissure
+    }
+
+    fn known(fissures: &[Fissure]) -> HashMap<String, Fissure> {
+        fissures
+            .iter()
+            .map(|fissure| (fissure.id.clone(), fissure.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn snooze_ends_before_expiry() {
+        let duration = Duration::from_secs(10 * 60);
+        let in_minutes = |minutes| now() + time::Duration::minutes(minutes);
+        assert_eq!(
+            snooze_due(&fissure("a", Tier::Lith, 30), now(), duration),
+            in_minutes(10)
+        );
+        assert_eq!(
+            snooze_due(&fissure("a", Tier::Lith, 5), now(), duration),
+            in_minutes(4)
+        );
+        // a fissure expiring within the margin is reminded of right away
+        assert_eq!(
+            snooze_due(&fissure("a", Tier::Lith, 0), now(), duration),
+            now()
+        );
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed_fissures() {
+        let kept = fissure("kept", Tier::Lith, 30);
+        let moved = fissure("moved", Tier::Meso, 30);
+        let expired = fissure("expired", Tier::Lith, 0);
+        let vanished = fissure("vanished", Tier::Neo, 30);
+        let mut known = known(&[
+            kept.clone(),
+            moved.clone(),
+            expired.clone(),
+            vanished.clone(),
+        ]);
+        let mut after = moved.clone();
+        after.node = "Apollo (Lua)".to_string();
+        let added = fissure("added", Tier::Axi, 60);
+
+        let events = diff_fissures(
+            &mut known,
+            vec![kept.clone(), after.clone(), added.clone()],
+            now(),
+        );
+        assert_eq!(
+            events,
+            [
+                FissureEvent::Removed {
+                    fissure: expired,
+                    reason: RemovalReason::Expired,
+                },
+                FissureEvent::Removed {
+                    fissure: vanished,
+                    reason: RemovalReason::Vanished,
+                },
+                FissureEvent::Changed {
+                    before: moved,
+                    after: after.clone(),
+                },
+                FissureEvent::Added(added.clone()),
+            ]
+        );
+        assert_eq!(known, self::known(&[kept, after, added]));
+    }
+
+    #[test]
+    fn diff_of_removed_fissure_marked_expired_early_is_expired() {
+        let mut fissure = fissure("a", Tier::Lith, 30);
+        fissure.expired = true;
+        let mut known = known(&[fissure.clone()]);
+        assert_eq!(
+            diff_fissures(&mut known, Vec::new(), now()),
+            [FissureEvent::Removed {
+                fissure,
+                reason: RemovalReason::Expired,
+            }]
+        );
+        assert!(known.is_empty());
+    }
+
+    #[test]
+    fn diff_is_sorted_by_kind_tier_and_expiry() {
+        let mut known = HashMap::new();
+        let current = vec![
+            fissure("axi", Tier::Axi, 10),
+            fissure("lith late", Tier::Lith, 50),
+            fissure("meso", Tier::Meso, 10),
+            fissure("lith early", Tier::Lith, 20),
+        ];
+        let events = diff_fissures(&mut known, current.clone(), now());
+        let ids = events
+            .iter()
+            .map(|event| event.fissure().id.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(ids, ["lith early", "lith late", "meso", "axi"]);
+        assert!(diff_fissures(&mut known, current, now()).is_empty());
     }
 }
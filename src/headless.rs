@@ -0,0 +1,293 @@
+use std::{error::Error, sync::Arc, time::Duration};
+
+use procmacros::Display;
+use serde_json::json;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tokio::sync::RwLock;
+
+use crate::{
+    cli::{LogFormat, EXIT_CONFIG},
+    config::{Config, CONFIG_PATH},
+    fissure_watcher::{self, Control, FissureWatcher},
+    inbox::{InboxEntry, Status},
+    notifications::NotificationAction,
+    util::comma_separated_string,
+};
+
+/// Exit status when the watcher stopped on its own
+pub const EXIT_FAILURE: i32 = 1;
+/// Longest wait for the notifications still held back to be delivered when shutting down
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+enum Level {
+    Info,
+    Warn,
+    Error,
+}
+
+/// Writes log lines to stdout
+struct Logger {
+    format: LogFormat,
+}
+
+impl Logger {
+    fn log(&self, level: Level, message: impl AsRef<str>) {
+        let time = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .unwrap_or_default();
+        match self.format {
+            LogFormat::Plain => println!(
+                "{} {:<5} {}",
+                time,
+                level.to_string().to_uppercase(),
+                message.as_ref()
+            ),
+            LogFormat::Json => println!(
+                "{}",
+                json!({
+                    "time": time,
+                    "level": level.to_string().to_lowercase(),
+                    "message": message.as_ref(),
+                })
+            ),
+        }
+    }
+}
+
+/// Runs the fissure watcher and the notification sinks without the terminal interface until SIGTERM or SIGINT.
+/// # Returns
+/// The exit status of the process.
+pub async fn run(log_format: LogFormat) -> i32 {
+    let logger = Logger { format: log_format };
+    let config = match load_config().await {
+        Ok(config) => config,
+        Err(e) => {
+            logger.log(
+                Level::Error,
+                format!("Failed to load {}: {}", CONFIG_PATH, e),
+            );
+            return EXIT_CONFIG;
+        }
+    };
+    logger.log(
+        Level::Info,
+        format!(
+            "Starting Reaper's Warframe Tools v{} without the terminal interface",
+            env!("CARGO_PKG_VERSION")
+        ),
+    );
+    let config = Arc::new(RwLock::new(config));
+    let mut watcher = FissureWatcher::new(Arc::clone(&config));
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            signal = &mut shutdown => {
+                logger.log(Level::Info, format!("Received {}, shutting down", signal));
+                finish_notifications(&logger, &config, &mut watcher).await;
+                return 0;
+            }
+            // the task only ends if it panicked, the other tasks keep the event channel open
+            result = &mut watcher.fissure_handle => {
+                let reason = result.err().map(|e| format!(": {}", e)).unwrap_or_default();
+                logger.log(Level::Error, format!("The fissure watcher stopped unexpectedly{}", reason));
+                return EXIT_FAILURE;
+            }
+            Some(event) = watcher.fissure_rx.recv() => {
+                on_event(&logger, &config, &mut watcher, event).await;
+            }
+        }
+    }
+}
+
+/// Stops the watcher task, which closes the channel to the notification task, and waits up to `SHUTDOWN_TIMEOUT`
+/// for that to deliver the notifications it still holds, logging the events reported meanwhile
+async fn finish_notifications(
+    logger: &Logger,
+    config: &Arc<RwLock<Config>>,
+    watcher: &mut FissureWatcher,
+) {
+    watcher.fissure_handle.abort();
+    let timeout = tokio::time::sleep(SHUTDOWN_TIMEOUT);
+    tokio::pin!(timeout);
+    loop {
+        tokio::select! {
+            _ = &mut watcher.notification_handle => return,
+            _ = &mut timeout => {
+                logger.log(
+                    Level::Warn,
+                    format!(
+                        "Notifications not delivered within {}s were discarded",
+                        SHUTDOWN_TIMEOUT.as_secs()
+                    ),
+                );
+                return;
+            }
+            Some(event) = watcher.fissure_rx.recv() => {
+                on_event(logger, config, watcher, event).await;
+            }
+        }
+    }
+}
+
+/// Loads the config file, creating the default one if there is none.
+/// Unlike the terminal interface, an invalid config is not replaced by the default config.
+async fn load_config() -> Result<Config, Box<dyn Error>> {
+    if !tokio::fs::try_exists(CONFIG_PATH).await? {
+        Config::create_default_file().await?;
+    }
+    Config::load().await
+}
+
+/// Resolves with the name of the signal that asked the process to stop
+async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                _ = terminate.recv() => "SIGTERM",
+                _ = tokio::signal::ctrl_c() => "SIGINT",
+            },
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                "SIGINT"
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+        "Ctrl+C"
+    }
+}
+
+async fn on_event(
+    logger: &Logger,
+    config: &RwLock<Config>,
+    watcher: &mut FissureWatcher,
+    event: fissure_watcher::Event,
+) {
+    match event {
+        fissure_watcher::Event::Fissures {
+            fissures,
+            filtered_fissures,
+            events,
+        } => {
+            logger.log(
+                Level::Info,
+                format!(
+                    "{} fissures, {} matching the filters, {} changes",
+                    fissures.len(),
+                    filtered_fissures.len(),
+                    events.len()
+                ),
+            );
+            for event in events {
+                logger.log(Level::Info, event.to_string());
+            }
+            watcher.update_fissures(fissures, filtered_fissures);
+        }
+        fissure_watcher::Event::NoNewFissures => logger.log(Level::Info, "No new fissures"),
+        fissure_watcher::Event::Err(e) => logger.log(Level::Error, e),
+        fissure_watcher::Event::Log(message) => logger.log(Level::Info, message),
+        fissure_watcher::Event::Notification(entry) => log_notification(logger, &entry),
+        fissure_watcher::Event::Alert(event) => {
+            logger.log(Level::Info, format!("Alert: {}", event))
+        }
+        fissure_watcher::Event::Action(action) => {
+            on_notification_action(logger, config, watcher, action).await
+        }
+        fissure_watcher::Event::Inbox(_)
+        | fissure_watcher::Event::Marks(_)
+        | fissure_watcher::Event::Reminders(_)
+        | fissure_watcher::Event::Statistics(_)
+        | fissure_watcher::Event::Prediction(_)
+        | fissure_watcher::Event::Schedule { .. } => {}
+    }
+}
+
+fn log_notification(logger: &Logger, entry: &InboxEntry) {
+    let fissures = comma_separated_string(&entry.fissures);
+    match entry.status {
+        Status::Sent => {
+            for delivery in &entry.deliveries {
+                match &delivery.error {
+                    None => logger.log(
+                        Level::Info,
+                        format!(
+                            "Sent {} notification to {}: {}",
+                            entry.event, delivery.sink, fissures
+                        ),
+                    ),
+                    Some(e) => logger.log(
+                        Level::Warn,
+                        format!(
+                            "Failed to send {} notification to {}: {}",
+                            entry.event, delivery.sink, e
+                        ),
+                    ),
+                }
+            }
+        }
+        status => logger.log(
+            Level::Info,
+            format!("{} {} notification: {}", status, entry.event, fissures),
+        ),
+    }
+}
+
+/// Carries out the actions clicked on desktop notifications, if the desktop sink is used without the interface
+async fn on_notification_action(
+    logger: &Logger,
+    config: &RwLock<Config>,
+    watcher: &mut FissureWatcher,
+    action: NotificationAction,
+) {
+    match action {
+        NotificationAction::Snooze(fissures) => {
+            let minutes = config.read().await.snooze_minutes;
+            let control = Control::Snooze {
+                ids: fissures.iter().map(|fissure| fissure.id.clone()).collect(),
+                duration: Duration::from_secs(minutes * 60),
+            };
+            match watcher.control(control).await {
+                Ok(()) => logger.log(
+                    Level::Info,
+                    format!(
+                        "Snoozed {} for {} minutes",
+                        comma_separated_string(&fissures),
+                        minutes
+                    ),
+                ),
+                Err(e) => logger.log(Level::Error, e),
+            }
+        }
+        NotificationAction::IgnoreNode(node) => {
+            let mut config = config.write().await;
+            if !config.ignored_nodes.contains(&node) {
+                config.ignored_nodes.push(node.clone());
+            }
+            watcher.refilter(&config);
+            drop(config);
+            match Config::save_ignored_node(&node).await {
+                Ok(()) => logger.log(
+                    Level::Info,
+                    format!(
+                        "Ignoring fissures on {}, added it to ignored_nodes in {}",
+                        node, CONFIG_PATH
+                    ),
+                ),
+                Err(e) => logger.log(
+                    Level::Error,
+                    format!(
+                        "Ignoring fissures on {} until the next restart, saving it to {} failed: {}",
+                        node, CONFIG_PATH, e
+                    ),
+                ),
+            }
+        }
+        NotificationAction::Open(_) => {}
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+};
+
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    config::Config,
+    filters::{Factions, Tier},
+    mission_type::MissionType,
+    models::Fissure,
+};
+
+pub const HISTORY_PATH: &str = "reapers-wf-fissures.jsonl";
+
+/// A fissure as recorded in the history file, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    pub id: String,
+    pub tier: Tier,
+    pub mission_type: MissionType,
+    pub node: String,
+    pub enemy: Factions,
+    pub is_storm: bool,
+    pub is_hard: bool,
+    #[serde(with = "time::serde::iso8601")]
+    pub activation: OffsetDateTime,
+    #[serde(with = "time::serde::iso8601")]
+    pub expiry: OffsetDateTime,
+}
+
+impl From<&Fissure> for HistoryRecord {
+    fn from(fissure: &Fissure) -> Self {
+        Self {
+            id: fissure.id.clone(),
+            tier: fissure.tier,
+            mission_type: fissure.mission_type,
+            node: fissure.node.clone(),
+            enemy: fissure.enemy,
+            is_storm: fissure.is_storm,
+            is_hard: fissure.is_hard,
+            activation: fissure.activation,
+            expiry: fissure.expiry,
+        }
+    }
+}
+
+impl HistoryRecord {
+    pub fn duration(&self) -> Duration {
+        self.expiry - self.activation
+    }
+
+    /// Whether the recorded fissure passes the filters of the given config
+    pub fn matches(&self, config: &Config) -> bool {
+        !config.ignored_nodes.contains(&self.node)
+            && config.profiles().any(|profile| {
+                profile.filter.matches_values(
+                    self.mission_type,
+                    self.tier,
+                    self.enemy,
+                    self.is_storm,
+                )
+            })
+    }
+
+    /// Describes the kind of fissure, without the node
+    pub fn kind(&self) -> String {
+        format!(
+            "{}{} {}",
+            if self.is_hard { "SP " } else { "" },
+            self.tier,
+            self.mission_type
+        )
+    }
+}
+
+/// Every fissure ever seen by the watcher, backed by an append-only file
+#[derive(Default)]
+pub struct History {
+    records: Vec<HistoryRecord>,
+    ids: HashSet<String>,
+}
+
+impl History {
+    /// Loads the history file, skipping lines that cannot be parsed.
+    /// # Returns
+    /// The history and the number of skipped lines.
+    pub async fn load() -> Result<(History, usize), Box<dyn Error>> {
+        let text = match tokio::fs::read_to_string(HISTORY_PATH).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok((History::default(), 0))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(History::parse(&text))
+    }
+
+    /// Parses the JSON lines of a history file, skipping lines that cannot be parsed.
+    /// # Returns
+    /// The history and the number of skipped lines.
+    fn parse(text: &str) -> (History, usize) {
+        let mut history = History::default();
+        let mut skipped = 0;
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str::<HistoryRecord>(line) {
+                Ok(record) => history.insert(record),
+                Err(_) => skipped += 1,
+            }
+        }
+        (history, skipped)
+    }
+
+    fn insert(&mut self, record: HistoryRecord) {
+        if self.ids.insert(record.id.clone()) {
+            self.records.push(record);
+        }
+    }
+
+    /// Records the given fissures, appending those that were not recorded yet to the history file.
+    /// # Returns
+    /// Whether any fissure was recorded.
+    pub async fn record(&mut self, fissures: &[&Fissure]) -> Result<bool, Box<dyn Error>> {
+        let new = fissures
+            .iter()
+            .filter(|fissure| !self.ids.contains(&fissure.id))
+            .map(|fissure| HistoryRecord::from(*fissure))
+            .collect::<Vec<HistoryRecord>>();
+        if new.is_empty() {
+            return Ok(false);
+        }
+        let mut lines = String::new();
+        for record in &new {
+            lines.push_str(&serde_json::to_string(record)?);
+            lines.push('\n');
+        }
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(HISTORY_PATH)
+            .await?;
+        file.write_all(lines.as_bytes()).await?;
+        for record in new {
+            self.insert(record);
+        }
+        Ok(true)
+    }
+
+    pub fn records(&self) -> &[HistoryRecord] {
+        &self.records
+    }
+}
+
+/// How often a group of fissures was seen and how long they lasted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupStatistics {
+    pub name: String,
+    pub count: usize,
+    pub average_duration: Duration,
+}
+
+/// How much time passed between occurrences of a kind of fissure matching the filters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GapStatistics {
+    pub name: String,
+    pub count: usize,
+    /// `None` if the kind was only seen once
+    pub average_gap: Option<Duration>,
+    pub longest_gap: Option<Duration>,
+    pub last_seen: OffsetDateTime,
+}
+
+/// Statistics about the recorded fissure history
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Statistics {
+    pub total: usize,
+    /// Activation of the oldest recorded fissure
+    pub since: Option<OffsetDateTime>,
+    pub tiers: Vec<GroupStatistics>,
+    pub missions: Vec<GroupStatistics>,
+    /// Gaps between fissures matching the filters, grouped by kind
+    pub matches: Vec<GapStatistics>,
+}
+
+impl Statistics {
+    pub fn new(records: &[HistoryRecord], config: &Config) -> Self {
+        let mut tiers = group(records, |record| record.tier.to_string());
+        // keep the tiers in their natural order instead of by count
+        tiers.sort_by_key(|stats| {
+            Tier::VARIANTS
+                .iter()
+                .position(|tier| tier.to_string() == stats.name)
+        });
+        let mut missions = group(records, |record| record.mission_type.to_string());
+        missions.sort_by(|a, b| b.count.cmp(&a.count).then(a.name.cmp(&b.name)));
+        // activations of each kind of matching fissure
+        let mut activations = BTreeMap::<String, Vec<OffsetDateTime>>::new();
+        for record in records.iter().filter(|record| record.matches(config)) {
+            activations
+                .entry(record.kind())
+                .or_default()
+                .push(record.activation);
+        }
+        let matches = activations
+            .into_iter()
+            .map(|(name, mut activations)| {
+                activations.sort();
+                let gaps = activations
+                    .windows(2)
+                    .map(|pair| pair[1] - pair[0])
+                    .collect::<Vec<Duration>>();
+                GapStatistics {
+                    name,
+                    count: activations.len(),
+                    average_gap: (!gaps.is_empty())
+                        .then(|| gaps.iter().copied().sum::<Duration>() / gaps.len() as u32),
+                    longest_gap: gaps.iter().copied().max(),
+                    last_seen: *activations.last().unwrap(),
+                }
+            })
+            .collect();
+        Self {
+            total: records.len(),
+            since: records.iter().map(|record| record.activation).min(),
+            tiers,
+            missions,
+            matches,
+        }
+    }
+}
+
+/// Counts the records and averages their duration per group
+fn group(
+    records: &[HistoryRecord],
+    key: impl Fn(&HistoryRecord) -> String,
+) -> Vec<GroupStatistics> {
+    let mut groups = HashMap::<String, (usize, Duration)>::new();
+    for record in records {
+        let (count, total) = groups.entry(key(record)).or_default();
+        *count += 1;
+        *total += record.duration();
+    }
+    groups
+        .into_iter()
+        .map(|(name, (count, total))| GroupStatistics {
+            name,
+            count,
+            average_duration: total / count as u32,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two Lith and one Axi Disruption, one Lith Survival, a duplicate and a broken line
+    const HISTORY: &str = r#"{"id":"1","tier":"Lith","mission_type":"Disruption","node":"Ukko (Void)","enemy":"Orokin","is_storm":false,"is_hard":false,"activation":"2024-01-01T10:00:00Z","expiry":"2024-01-01T11:00:00Z"}
+{"id":"2","tier":"Lith","mission_type":"Survival","node":"Hydron (Sedna)","enemy":"Grineer","is_storm":false,"is_hard":false,"activation":"2024-01-01T10:30:00Z","expiry":"2024-01-01T12:30:00Z"}
+{"id":"1","tier":"Lith","mission_type":"Disruption","node":"Ukko (Void)","enemy":"Orokin","is_storm":false,"is_hard":false,"activation":"2024-01-01T10:00:00Z","expiry":"2024-01-01T11:00:00Z"}
+{"id":"3","tier":"Axi","mission_type":"Disruption","node":"Kappa (Sedna)","enemy":"Grineer","is_storm":false,"is_hard":false,"activation":"2024-01-01T11:00:00Z","expiry":"2024-01-01T12:00:00Z"}
+{"id":"4","tier":"Lith",
+{"id":"5","tier":"Lith","mission_type":"Disruption","node":"Ukko (Void)","enemy":"Orokin","is_storm":false,"is_hard":false,"activation":"2024-01-01T14:00:00Z","expiry":"2024-01-01T15:30:00Z"}
+"#;
+
+    fn hours(hours: f64) -> Duration {
+        Duration::seconds_f64(hours * 3600.0)
+    }
+
+    #[test]
+    fn statistics_count_tiers_missions_and_matches() {
+        let (history, skipped) = History::parse(HISTORY);
+        assert_eq!(skipped, 1);
+        assert_eq!(history.records().len(), 4);
+
+        let statistics = Statistics::new(history.records(), &Config::default());
+        assert_eq!(statistics.total, 4);
+        assert_eq!(
+            statistics.since,
+            Some(OffsetDateTime::from_unix_timestamp(1_704_103_200).unwrap())
+        );
+        let group = |name: &str, count, average: f64| GroupStatistics {
+            name: name.to_string(),
+            count,
+            average_duration: hours(average),
+        };
+        assert_eq!(
+            statistics.tiers,
+            [group("Lith", 3, 1.5), group("Axi", 1, 1.0)]
+        );
+        assert_eq!(
+            statistics.missions,
+            [group("Disruption", 3, 7.0 / 6.0), group("Survival", 1, 2.0)]
+        );
+        // the default config only matches Disruption
+        let names = statistics
+            .matches
+            .iter()
+            .map(|stats| (stats.name.as_str(), stats.count))
+            .collect::<Vec<(&str, usize)>>();
+        assert_eq!(names, [("Axi Disruption", 1), ("Lith Disruption", 2)]);
+        assert_eq!(statistics.matches[0].average_gap, None);
+        assert_eq!(statistics.matches[1].average_gap, Some(hours(4.0)));
+        assert_eq!(statistics.matches[1].longest_gap, Some(hours(4.0)));
+    }
+}
@@ -0,0 +1,401 @@
+use std::{error::Error, path::Path};
+
+use procmacros::Display;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::io::AsyncWriteExt;
+
+use crate::{models::Fissure, notifications::SinkKind, util::local_offset};
+
+pub const INBOX_PATH: &str = "reapers-wf-notifications.jsonl";
+/// How many entries are kept in memory, older ones are only in the file
+const MAX_ENTRIES: usize = 1000;
+
+/// What happened to a notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Display)]
+pub enum Status {
+    /// Handed to the sinks
+    Sent,
+    /// Held back during quiet hours, to be summarised later
+    Held,
+    /// Discarded during quiet hours
+    Dropped,
+}
+
+/// The result of delivering a notification to one sink
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub sink: SinkKind,
+    /// `None` if the delivery succeeded
+    pub error: Option<String>,
+}
+
+/// A notification the app sent or suppressed, one JSON object per line in the inbox file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InboxEntry {
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    /// The kind of event, "new" or "expiring"
+    pub event: String,
+    pub status: Status,
+    #[serde(default)]
+    pub deliveries: Vec<Delivery>,
+    pub fissures: Vec<Fissure>,
+}
+
+impl InboxEntry {
+    /// Whether any sink failed to deliver the notification
+    pub fn failed(&self) -> bool {
+        self.deliveries
+            .iter()
+            .any(|delivery| delivery.error.is_some())
+    }
+
+    /// One line describing the entry
+    pub fn summary(&self) -> String {
+        let time_format = time::format_description::parse("[hour]:[minute]:[second]").unwrap();
+        let outcome = match self.status {
+            Status::Sent if self.failed() => "Failed".to_string(),
+            Status::Sent => self
+                .deliveries
+                .iter()
+                .map(|delivery| delivery.sink.to_string())
+                .collect::<Vec<String>>()
+                .join(", "),
+            status => status.to_string(),
+        };
+        let fissures = match self.fissures.as_slice() {
+            [fissure] => fissure.to_string(),
+            fissures => format!("{} fissures", fissures.len()),
+        };
+        format!(
+            "{} {} {} -> {}",
+            self.time
+                .to_offset(local_offset())
+                .format(&time_format)
+                .unwrap(),
+            self.event,
+            fissures,
+            outcome
+        )
+    }
+}
+
+/// Loads the most recent entries of the inbox file, skipping lines that cannot be parsed.
+/// # Returns
+/// The entries, oldest first, and the number of skipped lines.
+pub async fn load() -> Result<(Vec<InboxEntry>, usize), Box<dyn Error>> {
+    load_from(Path::new(INBOX_PATH)).await
+}
+
+async fn load_from(path: &Path) -> Result<(Vec<InboxEntry>, usize), Box<dyn Error>> {
+    let text = match tokio::fs::read_to_string(path).await {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok((Vec::new(), 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        match serde_json::from_str::<InboxEntry>(line) {
+            Ok(entry) => entries.push(entry),
+            Err(_) => skipped += 1,
+        }
+    }
+    let excess = entries.len().saturating_sub(MAX_ENTRIES);
+    entries.drain(..excess);
+    Ok((entries, skipped))
+}
+
+/// Appends the entry to the inbox file
+pub async fn append(entry: &InboxEntry) -> Result<(), Box<dyn Error + Send + Sync>> {
+    append_to(Path::new(INBOX_PATH), entry).await
+}
+
+async fn append_to(path: &Path, entry: &InboxEntry) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut line = serde_json::to_string(entry)?;
+    line.push('\n');
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?
+        .write_all(line.as_bytes())
+        .await?;
+    Ok(())
+}
+
+/// The notifications tab, listing the newest entry first
+#[derive(Default)]
+pub struct Inbox {
+    entries: Vec<InboxEntry>,
+    state: ListState,
+}
+
+impl Inbox {
+    pub fn set_entries(&mut self, entries: Vec<InboxEntry>) {
+        self.entries = entries;
+        self.state.select(None);
+    }
+
+    pub fn push(&mut self, entry: InboxEntry) {
+        self.entries.push(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        // keep the same entry selected, the list shows the newest entry first
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(self.entries.len() - 1)));
+        }
+    }
+
+    /// The highlighted entry
+    pub fn selected(&self) -> Option<&InboxEntry> {
+        let i = self.state.selected()?;
+        self.entries.iter().rev().nth(i)
+    }
+
+    pub fn next(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.entries.len() => i + 1,
+            Some(_) => 0,
+            None => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(0) | None => self.entries.len() - 1,
+            Some(i) => i - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn draw(&mut self, f: &mut Frame, area: Rect) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(area);
+        let items = self
+            .entries
+            .iter()
+            .rev()
+            .map(|entry| {
+                let style = match entry.status {
+                    Status::Sent if entry.failed() => Style::default().fg(Color::Red),
+                    Status::Sent => Style::default(),
+                    Status::Held | Status::Dropped => Style::default().fg(Color::DarkGray),
+                };
+                ListItem::new(entry.summary()).style(style)
+            })
+            .collect::<Vec<ListItem>>();
+        let list = List::new(items)
+            .block(Block::default().title("Notifications"))
+            .highlight_style(Style::default().bold());
+        f.render_stateful_widget(list, chunks[0], &mut self.state);
+        let details = match self.selected() {
+            Some(entry) => details(entry),
+            None => vec![Line::from("Select a notification with Up and Down")],
+        };
+        let paragraph = Paragraph::new(details)
+            .block(Block::default().borders(Borders::LEFT).title("Details"))
+            .wrap(Wrap { trim: false });
+        f.render_widget(paragraph, chunks[1]);
+    }
+}
+
+/// Describes every delivery and fissure of the entry
+fn details(entry: &InboxEntry) -> Vec<Line<'static>> {
+    let time_format =
+        time::format_description::parse("[year]-[month]-[day] [hour]:[minute]:[second]").unwrap();
+    let mut lines = vec![
+        Line::from(format!(
+            "Time: {}",
+            entry
+                .time
+                .to_offset(local_offset())
+                .format(&time_format)
+                .unwrap()
+        )),
+        Line::from(format!("Event: {}", entry.event)),
+        Line::from(format!("Status: {}", entry.status)),
+        Line::from(""),
+    ];
+    if !entry.deliveries.is_empty() {
+        lines.push(Line::from("Sinks:".bold()));
+        for delivery in &entry.deliveries {
+            lines.push(match &delivery.error {
+                None => Line::from(format!("  {}: delivered", delivery.sink)),
+                Some(error) => Line::from(Span::styled(
+                    format!("  {}: {}", delivery.sink, error),
+                    Style::default().fg(Color::Red),
+                )),
+            });
+        }
+        lines.push(Line::from(""));
+    }
+    lines.push(Line::from("Fissures:".bold()));
+    for fissure in &entry.fissures {
+        lines.push(Line::from(format!("  {}", fissure)));
+    }
+    lines.push(Line::from(""));
+    lines.push(Line::from(
+        "Enter: show the fissure in the Fissures tab".italic(),
+    ));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, status: Status, deliveries: &[(SinkKind, Option<&str>)]) -> InboxEntry {
+        let time = OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap();
+        InboxEntry {
+            time,
+            event: "new".to_string(),
+            status,
+            deliveries: deliveries
+                .iter()
+                .map(|(sink, error)| Delivery {
+                    sink: *sink,
+                    error: error.map(|error| error.to_string()),
+                })
+                .collect(),
+            fissures: vec![Fissure::sample(
+                id,
+                "Hydron (Sedna)",
+                time + time::Duration::HOUR,
+            )],
+        }
+    }
+
+    fn ids(entries: &[InboxEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.fissures[0].id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn summary_shows_the_outcome() {
+        let sent = entry(
+            "a",
+            Status::Sent,
+            &[(SinkKind::Desktop, None), (SinkKind::Webhook, None)],
+        );
+        assert!(!sent.failed());
+        assert!(sent.summary().ends_with("-> Desktop, Webhook"));
+        let failed = entry(
+            "a",
+            Status::Sent,
+            &[
+                (SinkKind::Desktop, None),
+                (SinkKind::Webhook, Some("timed out")),
+            ],
+        );
+        assert!(failed.failed());
+        assert!(failed.summary().ends_with("-> Failed"));
+        assert!(entry("a", Status::Held, &[]).summary().ends_with("-> Held"));
+    }
+
+    #[tokio::test]
+    async fn appended_entries_load_oldest_first() {
+        let path =
+            std::env::temp_dir().join(format!("reapers-wf-inbox-{}.jsonl", std::process::id()));
+        let entries = [
+            entry("a", Status::Sent, &[(SinkKind::Desktop, Some("no daemon"))]),
+            entry("b", Status::Dropped, &[]),
+        ];
+        for entry in &entries {
+            append_to(&path, entry).await.unwrap();
+        }
+        tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .await
+            .unwrap()
+            .write_all(b"not json\n")
+            .await
+            .unwrap();
+
+        let loaded = load_from(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        let (loaded, skipped) = loaded.unwrap();
+        assert_eq!(loaded, entries);
+        assert_eq!(skipped, 1);
+        assert_eq!(load_from(&path).await.unwrap(), (Vec::new(), 0));
+    }
+
+    #[test]
+    fn lists_the_newest_entry_first_and_keeps_the_selection() {
+        let mut inbox = Inbox::default();
+        inbox.set_entries(vec![
+            entry("a", Status::Sent, &[]),
+            entry("b", Status::Sent, &[]),
+        ]);
+        assert_eq!(inbox.selected(), None);
+        inbox.next();
+        assert_eq!(inbox.selected().unwrap().fissures[0].id, "b");
+        inbox.next();
+        assert_eq!(inbox.selected().unwrap().fissures[0].id, "a");
+        inbox.next();
+        assert_eq!(inbox.selected().unwrap().fissures[0].id, "b");
+        inbox.previous();
+        assert_eq!(inbox.selected().unwrap().fissures[0].id, "a");
+
+        inbox.push(entry("c", Status::Sent, &[]));
+        assert_eq!(inbox.selected().unwrap().fissures[0].id, "a");
+        assert_eq!(ids(&inbox.entries), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn evicts_the_oldest_entries_over_the_cap() {
+        let mut inbox = Inbox::default();
+        for i in 0..MAX_ENTRIES {
+            inbox.push(entry(&i.to_string(), Status::Sent, &[]));
+        }
+        inbox.previous();
+        assert_eq!(inbox.selected().unwrap().fissures[0].id, "0");
+
+        inbox.push(entry("new", Status::Sent, &[]));
+        assert_eq!(inbox.entries.len(), MAX_ENTRIES);
+        assert_eq!(inbox.entries[0].fissures[0].id, "1");
+        assert_eq!(inbox.entries.last().unwrap().fissures[0].id, "new");
+        // the selected entry was evicted, the oldest one is selected instead
+        assert_eq!(inbox.selected().unwrap().fissures[0].id, "1");
+    }
+
+    #[tokio::test]
+    async fn loads_only_the_newest_entries_over_the_cap() {
+        let path =
+            std::env::temp_dir().join(format!("reapers-wf-inbox-cap-{}.jsonl", std::process::id()));
+        let mut text = String::new();
+        for i in 0..MAX_ENTRIES + 5 {
+            text.push_str(
+                &serde_json::to_string(&entry(&i.to_string(), Status::Sent, &[])).unwrap(),
+            );
+            text.push('\n');
+        }
+        tokio::fs::write(&path, text).await.unwrap();
+        let loaded = load_from(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        let (loaded, _) = loaded.unwrap();
+        assert_eq!(loaded.len(), MAX_ENTRIES);
+        assert_eq!(ids(&loaded[..1]), ["5"]);
+    }
+}
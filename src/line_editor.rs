@@ -0,0 +1,237 @@
+use std::{error::Error, path::Path};
+
+use tokio::io::AsyncWriteExt;
+
+pub const HISTORY_PATH: &str = "reapers-wf-history.txt";
+/// How many lines of history are kept in memory and loaded from the history file
+const MAX_HISTORY: usize = 500;
+
+/// A single line text input with a cursor and a recallable history of submitted lines
+#[derive(Default)]
+pub struct LineEditor {
+    buffer: String,
+    /// Position of the cursor in chars, from 0 up to and including the length of the buffer
+    cursor: usize,
+    history: Vec<String>,
+    /// Index into `history` of the entry currently being shown, `None` while editing a new line
+    history_index: Option<usize>,
+    /// The line that was being edited before browsing the history
+    draft: String,
+}
+
+impl LineEditor {
+    pub fn with_history(history: Vec<String>) -> Self {
+        Self {
+            history,
+            ..Default::default()
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.buffer
+    }
+
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Replaces the whole line and moves the cursor to its end
+    pub fn set_text(&mut self, text: String) {
+        self.cursor = text.chars().count();
+        self.buffer = text;
+    }
+
+    /// Converts a char position into a byte index into the buffer
+    fn byte_index(&self, char_index: usize) -> usize {
+        self.buffer
+            .char_indices()
+            .nth(char_index)
+            .map(|(i, _)| i)
+            .unwrap_or(self.buffer.len())
+    }
+
+    pub fn insert(&mut self, c: char) {
+        let index = self.byte_index(self.cursor);
+        self.buffer.insert(index, c);
+        self.cursor += 1;
+    }
+
+    /// Deletes the char before the cursor
+    pub fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let index = self.byte_index(self.cursor);
+            self.buffer.remove(index);
+        }
+    }
+
+    /// Deletes the char under the cursor
+    pub fn delete(&mut self) {
+        if self.cursor < self.buffer.chars().count() {
+            let index = self.byte_index(self.cursor);
+            self.buffer.remove(index);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.buffer.chars().count());
+    }
+
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn move_end(&mut self) {
+        self.cursor = self.buffer.chars().count();
+    }
+
+    /// Deletes the word before the cursor, along with any whitespace between it and the cursor
+    pub fn delete_word(&mut self) {
+        let chars = self.buffer.chars().collect::<Vec<char>>();
+        let mut start = self.cursor;
+        while start > 0 && chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        let (from, to) = (self.byte_index(start), self.byte_index(self.cursor));
+        self.buffer.replace_range(from..to, "");
+        self.cursor = start;
+    }
+
+    /// Deletes everything before the cursor
+    pub fn clear_before_cursor(&mut self) {
+        let index = self.byte_index(self.cursor);
+        self.buffer.replace_range(..index, "");
+        self.cursor = 0;
+    }
+
+    /// Shows the previous (older) history entry
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            Some(0) => return,
+            Some(i) => i - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.buffer.clone();
+                self.history.len() - 1
+            }
+        };
+        self.history_index = Some(index);
+        self.set_text(self.history[index].clone());
+    }
+
+    /// Shows the next (newer) history entry, or the draft once the end of the history is reached
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            None => {}
+            Some(i) if i + 1 < self.history.len() => {
+                self.history_index = Some(i + 1);
+                self.set_text(self.history[i + 1].clone());
+            }
+            Some(_) => {
+                self.history_index = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.set_text(draft);
+            }
+        }
+    }
+
+    /// Clears the line and returns it trimmed, adding it to the history if it is not empty or a repeat of the last entry.
+    /// # Returns
+    /// The submitted line, and whether it was added to the history.
+    pub fn submit(&mut self) -> (String, bool) {
+        let line = std::mem::take(&mut self.buffer).trim().to_string();
+        self.cursor = 0;
+        self.history_index = None;
+        self.draft.clear();
+        let is_new = !line.is_empty() && self.history.last() != Some(&line);
+        if is_new {
+            self.history.push(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        (line, is_new)
+    }
+}
+
+/// Loads the last `MAX_HISTORY` lines of the history file, returning an empty history if it does not exist.
+/// Older lines are removed from the file, which is only ever appended to otherwise.
+pub async fn load_history() -> Vec<String> {
+    load_history_from(Path::new(HISTORY_PATH)).await
+}
+
+async fn load_history_from(path: &Path) -> Vec<String> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(text) => {
+            let lines = text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| line.to_string())
+                .collect::<Vec<String>>();
+            let kept = lines[lines.len().saturating_sub(MAX_HISTORY)..].to_vec();
+            if kept.len() < text.lines().count() {
+                // the history is still usable if the file cannot be trimmed
+                let _ = tokio::fs::write(path, history_text(&kept)).await;
+            }
+            kept
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+fn history_text(lines: &[String]) -> String {
+    lines.iter().map(|line| format!("{}\n", line)).collect()
+}
+
+/// Appends a line to the history file, creating it if needed
+pub async fn append_history(line: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(HISTORY_PATH)
+        .await?;
+    file.write_all(format!("{}\n", line).as_bytes()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn loading_trims_the_history_file() {
+        let path =
+            std::env::temp_dir().join(format!("reapers-wf-history-{}.txt", std::process::id()));
+        let lines = (0..MAX_HISTORY + 10)
+            .map(|i| format!("interval {}", i + 1))
+            .collect::<Vec<String>>();
+        tokio::fs::write(&path, history_text(&lines)).await.unwrap();
+
+        let history = load_history_from(&path).await;
+        assert_eq!(history, lines[10..]);
+        let text = tokio::fs::read_to_string(&path).await.unwrap();
+        assert_eq!(text, history_text(&lines[10..]));
+
+        // blank lines are dropped too
+        tokio::fs::write(&path, "help\n\nquit\n").await.unwrap();
+        assert_eq!(load_history_from(&path).await, ["help", "quit"]);
+        assert_eq!(
+            tokio::fs::read_to_string(&path).await.unwrap(),
+            "help\nquit\n"
+        );
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        assert!(load_history_from(&path).await.is_empty());
+    }
+}
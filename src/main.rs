@@ -3,9 +3,27 @@ use std::{error::Error, time::Duration};
 extern crate procmacros;
 
 mod api;
+mod cli;
+mod commands;
 mod filters;
+mod headless;
+mod history;
+mod inbox;
+mod line_editor;
 mod mission_type;
 mod models;
+mod mqtt;
+mod notifications;
+mod prediction;
+mod query;
+mod relay;
+mod reminders;
+mod scheduler;
+mod server;
+mod state;
+mod template;
+#[cfg(test)]
+mod test_util;
 mod util;
 mod config;
 mod fissure_watcher;
@@ -15,9 +33,33 @@ mod ui;
 
 use mission_type::MissionType;
 
-#[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
-    let tick_rate = Duration::from_millis(20);
-    crate::crossterm::run(tick_rate).await?;
+fn main() -> Result<(), Box<dyn Error>> {
+    let mode = match cli::parse(std::env::args().skip(1)) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(cli::EXIT_USAGE);
+        }
+    };
+    // has to happen before the runtime starts its worker threads
+    util::init_local_offset();
+    let runtime = tokio::runtime::Runtime::new()?;
+    match mode {
+        cli::Mode::Help => println!("{}", cli::USAGE),
+        cli::Mode::Tui => runtime.block_on(async {
+            let tick_rate = Duration::from_millis(20);
+            crate::crossterm::run(tick_rate).await
+        })?,
+        cli::Mode::Fissures { selection, format } => {
+            let status = runtime.block_on(query::fissures(selection, format));
+            std::process::exit(status);
+        }
+        cli::Mode::Headless { log_format } => {
+            let status = runtime.block_on(headless::run(log_format));
+            // the notifications were delivered, the remaining tasks are not waited for
+            runtime.shutdown_background();
+            std::process::exit(status);
+        }
+    }
     Ok(())
 }
@@ -1,7 +1,7 @@
-use procmacros::{Display, FromStr};
+use procmacros::{Display, FromStr, Variants};
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug,Serialize,Deserialize,Clone,Copy,PartialEq,Eq,Hash,Display,FromStr)]
+#[derive(Debug,Serialize,Deserialize,Clone,Copy,PartialEq,Eq,Hash,Display,FromStr,Variants)]
 pub enum MissionType {
     Capture,
     Defense,
@@ -35,8 +35,3 @@ pub enum MissionType {
     Deception,
     Crossfire,
 }
-impl MissionType {
-    pub fn apply_filter(&self, value: &crate::models::Fissure) -> bool {
-        value.mission_type == *self
-    }
-}
@@ -1,17 +1,17 @@
 use std::fmt::Display;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::filters::{Factions, Tier};
 
-#[derive(Debug, Deserialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub struct Fissure {
     /// unique identifier for this object/event/thing
     pub id:	String,
     /// ISO-8601 formatted timestamp for when the event began
-    #[serde(with = "time::serde::iso8601")]
+    #[serde(with = "time::serde::rfc3339")]
     pub activation:	time::OffsetDateTime,
     /// ISO-8601 formatted timestamp for when the event is expected to end
-    #[serde(with = "time::serde::iso8601")]
+    #[serde(with = "time::serde::rfc3339")]
     pub expiry:	time::OffsetDateTime,
     /// Short-time-formatted duration string representing the start of the event
     #[serde(rename = "startString")]
@@ -60,6 +60,19 @@ impl Fissure {
             self.expiry.format(&time_format).unwrap(),
         ]
     }
+    /// Whether any of the fields describing the fissure differ, ignoring the relative time strings that change on every fetch
+    pub fn has_changed(&self, other: &Fissure) -> bool {
+        self.activation != other.activation
+            || self.expiry != other.expiry
+            || self.active != other.active
+            || self.node != other.node
+            || self.expired != other.expired
+            || self.mission_type != other.mission_type
+            || self.tier != other.tier
+            || self.enemy != other.enemy
+            || self.is_storm != other.is_storm
+            || self.is_hard != other.is_hard
+    }
     pub fn table_headers() -> Vec<String> {
         vec![
             "SP".to_string(),
@@ -70,4 +83,28 @@ impl Fissure {
             "Expiry".to_string(),
         ]
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+impl Fissure {
+    /// A Lith Survival fissure against the Grineer on the given node, active since an hour before its expiry
+    pub fn sample(id: &str, node: &str, expiry: time::OffsetDateTime) -> Self {
+        Fissure {
+            id: id.to_string(),
+            activation: expiry - time::Duration::HOUR,
+            expiry,
+            start_string: "-10m".to_string(),
+            active: true,
+            node: node.to_string(),
+            expired: false,
+            eta: "50m".to_string(),
+            mission_type: super::MissionType::Survival,
+            mission_key: "Survival".to_string(),
+            tier: Tier::Lith,
+            tier_num: 1,
+            enemy: Factions::Grineer,
+            enemy_key: Factions::Grineer,
+            is_storm: false,
+            is_hard: false,
+        }
+    }
+}
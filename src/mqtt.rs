@@ -0,0 +1,494 @@
+use std::{sync::Arc, time::Duration};
+
+use reqwest::Url;
+use rumqttc::{
+    AsyncClient, EventLoop, LastWill, MqttOptions, Outgoing, Packet, QoS, TlsConfiguration,
+    Transport,
+};
+use serde::{Deserialize, Deserializer};
+use serde_json::{json, Value};
+use tokio::{
+    sync::{mpsc, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{config::Config, filters::Tier, fissure_watcher, models::Fissure};
+
+/// Wait before reconnecting after the connection to the broker failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// Longest wait for the retained messages to be cleared when the broker settings change
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The broker the fissures are published to, for home automation such as Home Assistant
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+pub struct MqttConfig {
+    /// `mqtt://host:port`, or `mqtts://host:port` for TLS
+    #[serde(deserialize_with = "deserialize_url")]
+    pub url: Url,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    /// Prepended to all topics
+    #[serde(default = "default_topic_prefix")]
+    pub topic_prefix: String,
+    #[serde(default = "default_qos", deserialize_with = "deserialize_qos")]
+    pub qos: QoS,
+    /// Whether to publish Home Assistant discovery payloads so the sensors appear automatically
+    #[serde(default = "default_discovery")]
+    pub discovery: bool,
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+fn default_client_id() -> String {
+    "reapers-wf".to_string()
+}
+fn default_topic_prefix() -> String {
+    "reapers-wf".to_string()
+}
+fn default_qos() -> QoS {
+    QoS::AtMostOnce
+}
+fn default_discovery() -> bool {
+    true
+}
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+fn deserialize_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Url, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    let url = Url::parse(&text).map_err(serde::de::Error::custom)?;
+    if !matches!(url.scheme(), "mqtt" | "mqtts") || url.host_str().is_none() {
+        return Err(serde::de::Error::custom(format!(
+            "Invalid broker URL \"{}\", expected mqtt://host:port or mqtts://host:port",
+            text
+        )));
+    }
+    Ok(url)
+}
+fn deserialize_qos<'de, D: Deserializer<'de>>(deserializer: D) -> Result<QoS, D::Error> {
+    let qos = u8::deserialize(deserializer)?;
+    rumqttc::qos(qos)
+        .map_err(|_| serde::de::Error::custom(format!("Invalid QoS {}, expected 0, 1 or 2", qos)))
+}
+
+/// Leaves out the password, so that it does not end up in logs
+impl std::fmt::Debug for MqttConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MqttConfig")
+            .field("url", &self.url.as_str())
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("client_id", &self.client_id)
+            .field("topic_prefix", &self.topic_prefix)
+            .field("qos", &self.qos)
+            .field("discovery", &self.discovery)
+            .field("discovery_prefix", &self.discovery_prefix)
+            .finish()
+    }
+}
+
+impl MqttConfig {
+    fn topic(&self, name: &str) -> String {
+        format!("{}/{}", self.topic_prefix, name)
+    }
+
+    fn discovery_topic(&self, component: &str, object_id: &str) -> String {
+        format!(
+            "{}/{}/{}/{}/config",
+            self.discovery_prefix,
+            component,
+            self.node_id(),
+            object_id
+        )
+    }
+
+    /// Every topic retained messages are published to
+    fn retained_topics(&self) -> Vec<String> {
+        let mut topics = vec![self.topic("status"), self.topic("fissures")];
+        for tier in Tier::VARIANTS {
+            topics.push(self.topic(&format!("tiers/{}", tier_id(tier))));
+        }
+        if self.discovery {
+            for (component, object_id, _) in discovery(self) {
+                topics.push(self.discovery_topic(component, &object_id));
+            }
+        }
+        topics
+    }
+
+    /// The client id with everything but letters, digits and underscores replaced, as Home Assistant expects
+    fn node_id(&self) -> String {
+        self.client_id
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+
+    fn options(&self) -> MqttOptions {
+        let host = self.url.host_str().unwrap_or_default();
+        let tls = self.url.scheme() == "mqtts";
+        let port = self.url.port().unwrap_or(if tls { 8883 } else { 1883 });
+        let mut options = MqttOptions::new(&self.client_id, host, port);
+        options
+            .set_keep_alive(Duration::from_secs(30))
+            .set_last_will(LastWill::new(
+                self.topic("status"),
+                "offline",
+                QoS::AtLeastOnce,
+                true,
+            ));
+        if tls {
+            options.set_transport(Transport::tls_with_config(TlsConfiguration::Native));
+        }
+        if let Some(username) = &self.username {
+            options.set_credentials(username, self.password.clone().unwrap_or_default());
+        }
+        options
+    }
+}
+
+/// What the watcher publishes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MqttMessage {
+    /// The current fissures matching the filters, published as retained list and tier counts
+    Fissures(Vec<Fissure>),
+    /// A fissure matching the filters is "new", "expiring" or "removed"
+    Event {
+        kind: &'static str,
+        fissure: Fissure,
+    },
+}
+
+/// The connection to the broker configured when it was opened
+struct Connection {
+    config: MqttConfig,
+    client: AsyncClient,
+    poller: JoinHandle<()>,
+}
+
+impl Connection {
+    /// Connects in the background, signalling `connected_tx` whenever the broker accepted the connection
+    fn open(
+        config: MqttConfig,
+        connected_tx: mpsc::Sender<()>,
+        app_tx: mpsc::Sender<fissure_watcher::Event>,
+    ) -> Self {
+        let (client, event_loop) = AsyncClient::new(config.options(), 32);
+        let poller = tokio::spawn(poll(event_loop, connected_tx, app_tx));
+        Self {
+            config,
+            client,
+            poller,
+        }
+    }
+
+    /// Queues the message without waiting, so that an unreachable broker cannot hold up the watcher
+    fn publish(&self, topic: String, retain: bool, payload: String) -> Result<(), String> {
+        self.client
+            .try_publish(topic, self.config.qos, retain, payload)
+            .map_err(|e| e.to_string())
+    }
+
+    fn send(&self, message: &MqttMessage) -> Result<(), String> {
+        match message {
+            MqttMessage::Fissures(fissures) => {
+                let list = serde_json::to_string(fissures).map_err(|e| e.to_string())?;
+                self.publish(self.config.topic("fissures"), true, list)?;
+                for tier in Tier::VARIANTS {
+                    let count = fissures
+                        .iter()
+                        .filter(|fissure| fissure.tier == *tier)
+                        .count();
+                    let topic = self.config.topic(&format!("tiers/{}", tier_id(tier)));
+                    self.publish(topic, true, count.to_string())?;
+                }
+                Ok(())
+            }
+            MqttMessage::Event { kind, fissure } => {
+                let payload = json!({ "event_type": kind, "fissure": fissure }).to_string();
+                self.publish(self.config.topic("events"), false, payload)
+            }
+        }
+    }
+
+    /// Marks the app as online and publishes the discovery payloads, done whenever the connection is established
+    fn announce(&self) -> Result<(), String> {
+        self.publish(self.config.topic("status"), true, "online".to_string())?;
+        if !self.config.discovery {
+            return Ok(());
+        }
+        for (component, object_id, payload) in discovery(&self.config) {
+            let topic = self.config.discovery_topic(component, &object_id);
+            self.publish(topic, true, payload.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Clears the retained messages and disconnects, waiting a bounded time for that to reach the broker,
+    /// so that no stale fissures or sensors are left behind when the broker settings change or [mqtt] is removed
+    async fn close(mut self) {
+        for topic in self.config.retained_topics() {
+            let _ = self.publish(topic, true, String::new());
+        }
+        if self.client.try_disconnect().is_ok() {
+            let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut self.poller).await;
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.poller.abort();
+    }
+}
+
+/// Drives the connection until it is closed, reporting each failure to connect once until the connection is back
+async fn poll(
+    mut event_loop: EventLoop,
+    connected_tx: mpsc::Sender<()>,
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+) {
+    let mut failing = false;
+    loop {
+        match event_loop.poll().await {
+            Ok(rumqttc::Event::Incoming(Packet::ConnAck(_))) => {
+                failing = false;
+                let _ = connected_tx.send(()).await;
+            }
+            Ok(rumqttc::Event::Outgoing(Outgoing::Disconnect)) => return,
+            Ok(_) => {}
+            Err(e) => {
+                if !failing {
+                    let message = format!("MQTT connection failed: {}", e);
+                    let _ = app_tx.send(fissure_watcher::Event::Err(message)).await;
+                    failing = true;
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Lowercase name of the tier as used in topics and ids
+fn tier_id(tier: &Tier) -> String {
+    tier.to_string().to_lowercase()
+}
+
+/// The Home Assistant discovery payloads as (component, object id, payload)
+fn discovery(config: &MqttConfig) -> Vec<(&'static str, String, Value)> {
+    let node_id = config.node_id();
+    let device = json!({
+        "identifiers": [node_id],
+        "name": "Reaper's Warframe Tools",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let availability = config.topic("status");
+    let mut payloads = vec![
+        (
+            "sensor",
+            "fissures".to_string(),
+            json!({
+                "name": "Fissures",
+                "unique_id": format!("{}_fissures", node_id),
+                "state_topic": config.topic("fissures"),
+                "value_template": "{{ value_json | count }}",
+                "json_attributes_topic": config.topic("fissures"),
+                "json_attributes_template": "{{ {'fissures': value_json} | tojson }}",
+                "unit_of_measurement": "fissures",
+                "icon": "mdi:rhombus-split",
+                "availability_topic": availability,
+                "device": device,
+            }),
+        ),
+        (
+            "event",
+            "fissure_event".to_string(),
+            json!({
+                "name": "Fissure Event",
+                "unique_id": format!("{}_fissure_event", node_id),
+                "state_topic": config.topic("events"),
+                "event_types": ["new", "expiring", "removed"],
+                "availability_topic": availability,
+                "device": device,
+            }),
+        ),
+    ];
+    for tier in Tier::VARIANTS {
+        let id = tier_id(tier);
+        payloads.push((
+            "sensor",
+            id.clone(),
+            json!({
+                "name": format!("{} Fissures", tier),
+                "unique_id": format!("{}_{}", node_id, id),
+                "state_topic": config.topic(&format!("tiers/{}", id)),
+                "unit_of_measurement": "fissures",
+                "icon": "mdi:rhombus-split",
+                "availability_topic": availability,
+                "device": device,
+            }),
+        ));
+    }
+    payloads
+}
+
+/// Spawns the task that publishes the fissures to the broker configured in the config.
+/// It reconnects when the broker settings change and does nothing while no broker is configured.
+/// # Returns
+/// The sender to publish messages with, and the handle of the task.
+pub fn spawn(
+    config: Arc<RwLock<Config>>,
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+) -> (mpsc::Sender<MqttMessage>, JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::channel::<MqttMessage>(32);
+    let handle = tokio::spawn(async move {
+        let (connected_tx, mut connected_rx) = mpsc::channel::<()>(1);
+        let mut connection: Option<Connection> = None;
+        // republished on every connect, so that the retained list survives a restart of the broker
+        let mut fissures: Option<MqttMessage> = None;
+        // failures are reported once until publishing works again
+        let mut failing = false;
+        loop {
+            let result = tokio::select! {
+                message = rx.recv() => {
+                    let Some(message) = message else {
+                        return;
+                    };
+                    let mqtt = config.read().await.mqtt.clone();
+                    if connection.as_ref().map(|connection| &connection.config) != mqtt.as_ref() {
+                        if let Some(connection) = connection.take() {
+                            connection.close().await;
+                        }
+                        connection = mqtt.map(|mqtt| {
+                            Connection::open(mqtt, connected_tx.clone(), app_tx.clone())
+                        });
+                    }
+                    if matches!(message, MqttMessage::Fissures(_)) {
+                        fissures = Some(message.clone());
+                    }
+                    match &connection {
+                        Some(connection) => connection.send(&message),
+                        None => Ok(()),
+                    }
+                }
+                Some(()) = connected_rx.recv() => match &connection {
+                    Some(connection) => match connection.announce() {
+                        Ok(()) => match &fissures {
+                            Some(message) => connection.send(message),
+                            None => Ok(()),
+                        },
+                        Err(e) => Err(e),
+                    },
+                    None => Ok(()),
+                },
+            };
+            match result {
+                Ok(()) => failing = false,
+                Err(_) if failing => {}
+                Err(e) => {
+                    failing = true;
+                    let message = format!("MQTT publish failed: {}", e);
+                    if app_tx
+                        .send(fissure_watcher::Event::Err(message))
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    (tx, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: &str) -> Result<MqttConfig, toml::de::Error> {
+        toml::from_str(&format!("url = \"mqtt://localhost\"\n{}", extra))
+    }
+
+    #[test]
+    fn parses_broker_urls() {
+        assert_eq!(config("").unwrap().url.as_str(), "mqtt://localhost");
+        let url = toml::from_str::<MqttConfig>("url = \"mqtts://broker:8883\"")
+            .unwrap()
+            .url;
+        assert_eq!((url.scheme(), url.port()), ("mqtts", Some(8883)));
+        assert!(toml::from_str::<MqttConfig>("url = \"http://localhost\"").is_err());
+        assert!(toml::from_str::<MqttConfig>("url = \"mqtt://\"").is_err());
+    }
+
+    #[test]
+    fn parses_qos() {
+        assert_eq!(config("").unwrap().qos, QoS::AtMostOnce);
+        assert_eq!(config("qos = 1").unwrap().qos, QoS::AtLeastOnce);
+        assert_eq!(config("qos = 2").unwrap().qos, QoS::ExactlyOnce);
+        assert!(config("qos = 3").is_err());
+    }
+
+    #[test]
+    fn names_topics() {
+        let config = config("client_id = \"reapers-wf pc\"\ntopic_prefix = \"home/wf\"").unwrap();
+        assert_eq!(config.topic("tiers/lith"), "home/wf/tiers/lith");
+        assert_eq!(config.node_id(), "reapers_wf_pc");
+        assert_eq!(
+            config.discovery_topic("sensor", "fissures"),
+            "homeassistant/sensor/reapers_wf_pc/fissures/config"
+        );
+    }
+
+    #[test]
+    fn discovery_describes_every_sensor() {
+        let config = config("").unwrap();
+        let payloads = discovery(&config);
+        let ids = payloads
+            .iter()
+            .map(|(component, object_id, _)| format!("{}/{}", component, object_id))
+            .collect::<Vec<String>>();
+        assert_eq!(
+            ids,
+            [
+                "sensor/fissures",
+                "event/fissure_event",
+                "sensor/lith",
+                "sensor/meso",
+                "sensor/neo",
+                "sensor/axi",
+                "sensor/requiem",
+            ]
+        );
+        for (_, object_id, payload) in &payloads {
+            assert_eq!(payload["unique_id"], format!("reapers_wf_{}", object_id));
+            assert_eq!(payload["availability_topic"], "reapers-wf/status");
+            assert_eq!(payload["device"]["identifiers"][0], "reapers_wf");
+        }
+        assert_eq!(payloads[0].2["state_topic"], "reapers-wf/fissures");
+        assert_eq!(payloads[1].2["state_topic"], "reapers-wf/events");
+        assert_eq!(payloads[6].2["state_topic"], "reapers-wf/tiers/requiem");
+    }
+
+    #[test]
+    fn retained_topics_include_discovery_if_enabled() {
+        let topics = config("").unwrap().retained_topics();
+        assert_eq!(topics.len(), 2 + Tier::VARIANTS.len() + 7);
+        assert!(topics.contains(&"reapers-wf/tiers/axi".to_string()));
+        assert!(topics.contains(&"homeassistant/event/reapers_wf/fissure_event/config".to_string()));
+
+        let topics = config("discovery = false").unwrap().retained_topics();
+        assert_eq!(topics.len(), 2 + Tier::VARIANTS.len());
+    }
+
+    #[test]
+    fn debug_leaves_out_the_password() {
+        let debug = format!("{:?}", config("password = \"hunter2\"").unwrap());
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains("hunter2"));
+    }
+}
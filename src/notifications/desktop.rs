@@ -0,0 +1,191 @@
+use std::sync::{Arc, Mutex, OnceLock};
+
+use async_trait::async_trait;
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::sync::mpsc;
+
+use super::{NotificationAction, NotificationEvent, NotificationSink, NotifyResult};
+use crate::{config::Config, fissure_watcher, models::Fissure};
+
+const LOGO: &[u8] = include_bytes!("../../assets/logo-2_256px.png");
+static ICON_PATH: OnceLock<Option<String>> = OnceLock::new();
+
+/// How urgent the desktop notifications about fissures matching a profile are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    /// Usually stays visible until dismissed
+    Critical,
+}
+
+/// The "New Fissures" notification that is currently shown and updated with further new fissures
+#[derive(Default)]
+struct Shown {
+    id: Option<u32>,
+    fissures: Vec<Fissure>,
+}
+
+/// Shows notifications through the desktop's notification service
+pub struct DesktopSink {
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+    shown: Arc<Mutex<Shown>>,
+}
+
+impl DesktopSink {
+    pub fn new(app_tx: mpsc::Sender<fissure_watcher::Event>) -> Self {
+        Self {
+            app_tx,
+            shown: Arc::new(Mutex::new(Shown::default())),
+        }
+    }
+
+    /// Adds the fissures to the shown "New Fissures" notification, replacing it in place
+    async fn notify_new(&self, fissures: &[Fissure], config: &Config) -> NotifyResult {
+        let now = OffsetDateTime::now_utc();
+        let (id, fissures) = {
+            let mut shown = self.shown.lock().unwrap();
+            shown.fissures.retain(|fissure| fissure.expiry > now);
+            for fissure in fissures {
+                if !shown.fissures.iter().any(|known| known.id == fissure.id) {
+                    shown.fissures.push(fissure.clone());
+                }
+            }
+            (shown.id, shown.fissures.clone())
+        };
+        let event = NotificationEvent::NewFissures(fissures);
+        let mut notification = notification(&event, config, now);
+        if let Some(id) = id {
+            notification.id(id);
+        }
+        // the notification was replaced in place, so the existing listener reports its actions
+        let on_action = id.is_none().then(|| {
+            let (shown, app_tx) = (Arc::clone(&self.shown), self.app_tx.clone());
+            Box::new(move |action: &str, id: u32| {
+                let fissures = {
+                    let mut shown = shown.lock().unwrap();
+                    if shown.id != Some(id) {
+                        return;
+                    }
+                    std::mem::take(&mut *shown).fissures
+                };
+                send_action(&app_tx, action, &fissures);
+            }) as OnAction
+        });
+        let shown_id = tokio::task::spawn_blocking(move || show(notification, on_action)).await??;
+        self.shown.lock().unwrap().id = shown_id;
+        Ok(())
+    }
+
+    async fn notify_expiring(&self, event: &NotificationEvent, config: &Config) -> NotifyResult {
+        let notification = notification(event, config, OffsetDateTime::now_utc());
+        let (fissures, app_tx) = (event.fissures().to_vec(), self.app_tx.clone());
+        let on_action =
+            Box::new(move |action: &str, _: u32| send_action(&app_tx, action, &fissures))
+                as OnAction;
+        tokio::task::spawn_blocking(move || show(notification, Some(on_action))).await??;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl NotificationSink for DesktopSink {
+    async fn notify(&self, event: &NotificationEvent, config: &Config) -> NotifyResult {
+        match event {
+            NotificationEvent::NewFissures(fissures) => self.notify_new(fissures, config).await,
+            NotificationEvent::Expiring { .. } => self.notify_expiring(event, config).await,
+        }
+    }
+}
+
+/// Called from a separate thread with the action the user clicked, or "__closed", and the notification id
+type OnAction = Box<dyn FnOnce(&str, u32) + Send>;
+
+/// Builds the notification about the event, using the highest urgency of the profiles its fissures match
+fn notification(event: &NotificationEvent, config: &Config, now: OffsetDateTime) -> Notification {
+    let (summary, body) = config.templates.render(event, now);
+    let urgency = event
+        .fissures()
+        .iter()
+        .flat_map(|fissure| config.matching_profiles(fissure))
+        .map(|profile| profile.urgency)
+        .max()
+        .unwrap_or_default();
+    let mut notification = Notification::new();
+    notification.summary(&summary).body(&body);
+    if let Some(icon) = icon_path() {
+        notification.icon(icon);
+    }
+    set_interactive(&mut notification, urgency, event.fissures().len() == 1);
+    notification
+}
+
+/// The bundled logo, written to the temporary directory because notification services expect a path
+fn icon_path() -> Option<&'static str> {
+    ICON_PATH
+        .get_or_init(|| {
+            let path = std::env::temp_dir().join("reapers-wf-logo.png");
+            std::fs::write(&path, LOGO).ok()?;
+            Some(path.to_string_lossy().into_owned())
+        })
+        .as_deref()
+}
+
+/// Reports the clicked action to the app
+fn send_action(app_tx: &mpsc::Sender<fissure_watcher::Event>, action: &str, fissures: &[Fissure]) {
+    let action = match action {
+        "snooze" => NotificationAction::Snooze(fissures.to_vec()),
+        "ignore_node" => match fissures {
+            [fissure] => NotificationAction::IgnoreNode(fissure.node.clone()),
+            _ => return,
+        },
+        "open" | "default" => match fissures.first() {
+            Some(fissure) => NotificationAction::Open(fissure.clone()),
+            None => return,
+        },
+        _ => return,
+    };
+    let _ = app_tx.blocking_send(fissure_watcher::Event::Action(action));
+}
+
+/// Sets the urgency and the actions, ignoring a node is only offered for a single fissure
+#[cfg(all(unix, not(target_os = "macos")))]
+fn set_interactive(notification: &mut Notification, urgency: Urgency, single: bool) {
+    notification.urgency(match urgency {
+        Urgency::Low => notify_rust::Urgency::Low,
+        Urgency::Normal => notify_rust::Urgency::Normal,
+        Urgency::Critical => notify_rust::Urgency::Critical,
+    });
+    notification.action("snooze", "Snooze");
+    if single {
+        notification.action("ignore_node", "Ignore this node");
+    }
+    notification.action("open", "Open in app");
+}
+
+/// Only the notification services of Linux and the BSDs support urgencies and actions
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn set_interactive(_notification: &mut Notification, _urgency: Urgency, _single: bool) {}
+
+/// Shows the notification, blocking until the notification service answered, and listens for its actions.
+/// # Returns
+/// The id of the notification.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn show(notification: Notification, on_action: Option<OnAction>) -> NotifyResult<Option<u32>> {
+    let handle = notification.show()?;
+    let id = handle.id();
+    if let Some(on_action) = on_action {
+        std::thread::spawn(move || handle.wait_for_action(|action| on_action(action, id)));
+    }
+    Ok(Some(id))
+}
+
+/// Shows the notification, other platforms neither report actions nor allow replacing notifications
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn show(notification: Notification, _on_action: Option<OnAction>) -> NotifyResult<Option<u32>> {
+    notification.show()?;
+    Ok(None)
+}
@@ -0,0 +1,319 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use async_trait::async_trait;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use super::{NotificationEvent, NotificationSink, NotifyResult};
+use crate::{config::Config, filters::Tier, models::Fissure};
+
+/// A message posted through a webhook, remembered so that the expiry reminder can edit it
+struct PostedMessage {
+    webhook: String,
+    id: String,
+    expiry: OffsetDateTime,
+}
+
+/// The part of Discord's message object that is needed
+#[derive(Deserialize)]
+struct Message {
+    id: String,
+}
+
+/// Posts an embed per fissure to the Discord webhooks of the profiles it matches
+pub struct DiscordSink {
+    client: reqwest::Client,
+    /// Messages posted for each fissure, by fissure id
+    messages: Mutex<HashMap<String, Vec<PostedMessage>>>,
+}
+
+impl DiscordSink {
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            messages: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Posts a new message with the embed, waiting for Discord to return the created message
+    async fn post(&self, webhook: &str, embed: Value) -> NotifyResult<String> {
+        let mut url = Url::parse(webhook)?;
+        url.query_pairs_mut().append_pair("wait", "true");
+        let message = self
+            .client
+            .post(url)
+            .json(&json!({ "embeds": [embed] }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Message>()
+            .await?;
+        Ok(message.id)
+    }
+
+    /// Replaces the embed of a message previously posted through the webhook
+    async fn edit(&self, webhook: &str, id: &str, embed: Value) -> NotifyResult {
+        let mut url = Url::parse(webhook)?;
+        url.path_segments_mut()
+            .map_err(|_| format!("Invalid webhook URL: {}", webhook))?
+            .push("messages")
+            .push(id);
+        self.client
+            .patch(url)
+            .json(&json!({ "embeds": [embed] }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn announce(&self, fissure: &Fissure, config: &Config) -> NotifyResult {
+        let mut posted = Vec::new();
+        let mut result = Ok(());
+        for webhook in webhooks(fissure, config) {
+            match self.post(webhook, embed(fissure, false)).await {
+                Ok(id) => posted.push(PostedMessage {
+                    webhook: webhook.clone(),
+                    id,
+                    expiry: fissure.expiry,
+                }),
+                Err(e) => result = Err(e),
+            }
+        }
+        let mut messages = self.messages.lock().unwrap();
+        let now = OffsetDateTime::now_utc();
+        messages.retain(|_, posted| posted.iter().any(|message| message.expiry > now));
+        if !posted.is_empty() {
+            messages.insert(fissure.id.clone(), posted);
+        }
+        result
+    }
+
+    /// Marks the messages about the fissure as expiring, posting a new message to webhooks
+    /// the fissure was not announced to, e.g. because it was announced before a restart
+    async fn remind(&self, fissure: &Fissure, config: &Config) -> NotifyResult {
+        let posted = self
+            .messages
+            .lock()
+            .unwrap()
+            .remove(&fissure.id)
+            .unwrap_or_default();
+        let mut result = Ok(());
+        for message in &posted {
+            if let Err(e) = self
+                .edit(&message.webhook, &message.id, embed(fissure, true))
+                .await
+            {
+                result = Err(e);
+            }
+        }
+        for webhook in webhooks(fissure, config)
+            .into_iter()
+            .filter(|webhook| !posted.iter().any(|message| message.webhook == **webhook))
+        {
+            if let Err(e) = self.post(webhook, embed(fissure, true)).await {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl NotificationSink for DiscordSink {
+    async fn notify(&self, event: &NotificationEvent, config: &Config) -> NotifyResult {
+        match event {
+            NotificationEvent::NewFissures(fissures) => {
+                let mut result = Ok(());
+                for fissure in fissures {
+                    if let Err(e) = self.announce(fissure, config).await {
+                        result = Err(e);
+                    }
+                }
+                result
+            }
+            NotificationEvent::Expiring { fissure, .. } => self.remind(fissure, config).await,
+        }
+    }
+}
+
+/// The webhooks of all profiles the fissure matches, without duplicates
+fn webhooks<'a>(fissure: &Fissure, config: &'a Config) -> Vec<&'a String> {
+    let mut webhooks = config
+        .matching_profiles(fissure)
+        .into_iter()
+        .flat_map(|profile| profile.discord_webhooks.iter())
+        .collect::<Vec<&String>>();
+    webhooks.sort();
+    webhooks.dedup();
+    webhooks
+}
+
+/// Embed colour of each tier
+fn colour(tier: Tier) -> u32 {
+    match tier {
+        Tier::Lith => 0xB87333,
+        Tier::Meso => 0xC0C0C0,
+        Tier::Neo => 0xFFD700,
+        Tier::Axi => 0xE5E4E2,
+        Tier::Requiem => 0x8B0000,
+    }
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "Yes"
+    } else {
+        "No"
+    }
+}
+
+/// Builds the embed describing the fissure, `expiring` marks it as about to expire
+fn embed(fissure: &Fissure, expiring: bool) -> Value {
+    let field = |name: &str, value: String| json!({ "name": name, "value": value, "inline": true });
+    let expires = format!("<t:{}:R>", fissure.expiry.unix_timestamp());
+    json!({
+        "title": if expiring {
+            format!("Expiring: {}", fissure)
+        } else {
+            fissure.to_string()
+        },
+        "description": if expiring {
+            format!("This fissure expires {}", expires)
+        } else {
+            format!("New fissure, expires {}", expires)
+        },
+        "color": colour(fissure.tier),
+        "fields": [
+            field("Mission", fissure.mission_type.to_string()),
+            field("Node", fissure.node.clone()),
+            field("Faction", fissure.enemy.to_string()),
+            field("Steel Path", yes_no(fissure.is_hard).to_string()),
+            field("Void Storm", yes_no(fissure.is_storm).to_string()),
+            field("Expires", expires),
+        ],
+        "timestamp": fissure.activation.format(&Rfc3339).ok(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::{Method, StatusCode};
+
+    use super::*;
+    use crate::{mission_type::MissionType, test_util::MockServer};
+
+    /// Answers posts like Discord does with `wait=true`
+    fn discord(_: &crate::test_util::Recorded) -> (StatusCode, String) {
+        (StatusCode::OK, json!({ "id": "1234" }).to_string())
+    }
+
+    fn config(webhook: String) -> Config {
+        let mut config = Config::default();
+        config.default_profile.filter.mission_filter = vec![MissionType::Survival];
+        config.default_profile.discord_webhooks = vec![webhook];
+        config
+    }
+
+    fn fissure() -> Fissure {
+        let expiry = OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap();
+        Fissure::sample("f1", "Hydron (Sedna)", expiry)
+    }
+
+    #[tokio::test]
+    async fn posts_an_embed_and_edits_it_for_the_reminder() {
+        let server = MockServer::start(discord);
+        let config = config(server.url("/api/webhooks/1/token"));
+        let sink = DiscordSink::new();
+        let fissure = fissure();
+        sink.notify(
+            &NotificationEvent::NewFissures(vec![fissure.clone()]),
+            &config,
+        )
+        .await
+        .unwrap();
+        sink.notify(
+            &NotificationEvent::Expiring {
+                fissure: fissure.clone(),
+                seconds: 300,
+            },
+            &config,
+        )
+        .await
+        .unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(requests[0].uri, "/api/webhooks/1/token?wait=true");
+        let embed = &requests[0].json()["embeds"][0];
+        assert_eq!(embed["title"], "Lith Survival on Hydron (Sedna)");
+        assert_eq!(embed["color"], 0xB87333);
+        let timestamp = "<t:1704110400:R>";
+        assert_eq!(
+            embed["description"],
+            format!("New fissure, expires {}", timestamp)
+        );
+        let fields = embed["fields"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|field| {
+                (
+                    field["name"].as_str().unwrap(),
+                    field["value"].as_str().unwrap(),
+                )
+            })
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(
+            fields,
+            [
+                ("Mission", "Survival"),
+                ("Node", "Hydron (Sedna)"),
+                ("Faction", "Grineer"),
+                ("Steel Path", "No"),
+                ("Void Storm", "No"),
+                ("Expires", timestamp),
+            ]
+        );
+
+        // the reminder edits the posted message instead of posting a new one
+        assert_eq!(requests[1].method, Method::PATCH);
+        assert_eq!(requests[1].uri, "/api/webhooks/1/token/messages/1234");
+        let embed = &requests[1].json()["embeds"][0];
+        assert_eq!(embed["title"], "Expiring: Lith Survival on Hydron (Sedna)");
+        assert_eq!(
+            embed["description"],
+            format!("This fissure expires {}", timestamp)
+        );
+    }
+
+    #[tokio::test]
+    async fn posts_the_reminder_if_the_fissure_was_not_announced() {
+        let server = MockServer::start(discord);
+        let config = config(server.url("/api/webhooks/1/token"));
+        let event = NotificationEvent::Expiring {
+            fissure: fissure(),
+            seconds: 300,
+        };
+        DiscordSink::new().notify(&event, &config).await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, Method::POST);
+        assert_eq!(
+            requests[0].json()["embeds"][0]["title"],
+            "Expiring: Lith Survival on Hydron (Sedna)"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_failed_posts() {
+        let server = MockServer::start(|_| (StatusCode::NOT_FOUND, String::new()));
+        let config = config(server.url("/api/webhooks/1/token"));
+        let event = NotificationEvent::NewFissures(vec![fissure()]);
+        assert!(DiscordSink::new().notify(&event, &config).await.is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use procmacros::Display;
+use serde::Deserialize;
+use time::OffsetDateTime;
+use tokio::sync::{mpsc, RwLock};
+
+use super::{NotificationEvent, NotificationSink, NotifyResult};
+use crate::{
+    config::Config,
+    fissure_watcher,
+    models::Fissure,
+    util::{local_offset, table},
+};
+
+/// How the connection to the SMTP server is encrypted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Display)]
+pub enum Security {
+    /// Upgrade a plain connection with STARTTLS, port 587 by default
+    StartTls,
+    /// Connect with TLS right away, port 465 by default
+    Tls,
+    /// No encryption, port 25 by default. Only meant for local SMTP servers
+    None,
+}
+
+/// Whether the "Email" sink sends a message per event or a summary at regular intervals
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Display)]
+pub enum EmailMode {
+    Immediate,
+    /// A table of the new fissures every `digest_minutes`, expiry reminders are not sent
+    Digest,
+}
+
+/// The SMTP server and recipients of the "Email" sink
+#[derive(Clone, Deserialize)]
+pub struct EmailConfig {
+    pub server: String,
+    /// Depends on `security` if not set
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default = "default_security")]
+    pub security: Security,
+    /// No authentication if not set
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: Mailbox,
+    pub to: Vec<Mailbox>,
+    #[serde(default = "default_mode")]
+    pub mode: EmailMode,
+    #[serde(default = "default_digest_minutes")]
+    pub digest_minutes: u64,
+}
+
+fn default_security() -> Security {
+    Security::StartTls
+}
+fn default_mode() -> EmailMode {
+    EmailMode::Immediate
+}
+fn default_digest_minutes() -> u64 {
+    60
+}
+
+/// Leaves out the password, so that it does not end up in logs
+impl std::fmt::Debug for EmailConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EmailConfig")
+            .field("server", &self.server)
+            .field("port", &self.port)
+            .field("security", &self.security)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .field("from", &self.from)
+            .field("to", &self.to)
+            .field("mode", &self.mode)
+            .field("digest_minutes", &self.digest_minutes)
+            .finish()
+    }
+}
+
+impl EmailConfig {
+    fn port(&self) -> u16 {
+        self.port.unwrap_or(match self.security {
+            Security::StartTls => 587,
+            Security::Tls => 465,
+            Security::None => 25,
+        })
+    }
+
+    fn transport(&self) -> NotifyResult<AsyncSmtpTransport<Tokio1Executor>> {
+        let builder = match self.security {
+            Security::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&self.server)?
+            }
+            Security::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&self.server)?,
+            Security::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&self.server),
+        };
+        let mut builder = builder
+            .port(self.port())
+            .timeout(Some(Duration::from_secs(30)));
+        if let Some(username) = &self.username {
+            let password = self.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(builder.build())
+    }
+
+    /// Sends a plain text message to all recipients
+    async fn send(&self, subject: &str, body: String) -> NotifyResult {
+        if self.to.is_empty() {
+            return Err("the email has no recipients".into());
+        }
+        let mut message = Message::builder().from(self.from.clone()).subject(subject);
+        for to in &self.to {
+            message = message.to(to.clone());
+        }
+        let message = message.header(ContentType::TEXT_PLAIN).body(body)?;
+        self.transport()?.send(message).await?;
+        Ok(())
+    }
+}
+
+/// The new fissures collected for the next digest
+#[derive(Default)]
+struct Digest {
+    fissures: Vec<Fissure>,
+    /// Whether a task is waiting to send the digest
+    scheduled: bool,
+}
+
+impl Digest {
+    /// Adds the fissures that are not in the digest yet
+    fn add(&mut self, fissures: &[Fissure]) {
+        for fissure in fissures {
+            if !self.fissures.iter().any(|known| known.id == fissure.id) {
+                self.fissures.push(fissure.clone());
+            }
+        }
+    }
+
+    /// Empties the digest, returning the fissures that have not expired yet
+    fn take(&mut self, now: OffsetDateTime) -> Vec<Fissure> {
+        let fissures = std::mem::take(self).fissures;
+        fissures
+            .into_iter()
+            .filter(|fissure| fissure.expiry > now)
+            .collect()
+    }
+}
+
+/// Sends notifications by email, either right away or collected in a digest
+pub struct EmailSink {
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+    /// Read when a digest is sent, which is long after the fissures were collected
+    config: Arc<RwLock<Config>>,
+    digest: Arc<Mutex<Digest>>,
+}
+
+impl EmailSink {
+    pub fn new(app_tx: mpsc::Sender<fissure_watcher::Event>, config: Arc<RwLock<Config>>) -> Self {
+        Self {
+            app_tx,
+            config,
+            digest: Arc::new(Mutex::new(Digest::default())),
+        }
+    }
+
+    /// Adds the fissures to the next digest, scheduling it if it is the first addition
+    fn collect(&self, fissures: &[Fissure], email: &EmailConfig) {
+        let mut digest = self.digest.lock().unwrap();
+        digest.add(fissures);
+        if digest.scheduled {
+            return;
+        }
+        digest.scheduled = true;
+        let wait = Duration::from_secs(email.digest_minutes.max(1) * 60);
+        let (digest, config, app_tx) = (
+            Arc::clone(&self.digest),
+            Arc::clone(&self.config),
+            self.app_tx.clone(),
+        );
+        tokio::spawn(async move {
+            tokio::time::sleep(wait).await;
+            let fissures = digest.lock().unwrap().take(OffsetDateTime::now_utc());
+            let email = config.read().await.email.clone();
+            if let Err(e) = send_digest(&fissures, email.as_ref()).await {
+                let message = format!("Email digest failed: {}", e);
+                let _ = app_tx.send(fissure_watcher::Event::Err(message)).await;
+            }
+        });
+    }
+}
+
+/// Sends the fissures as a digest with the given config, nothing if there are no fissures
+async fn send_digest(fissures: &[Fissure], email: Option<&EmailConfig>) -> NotifyResult {
+    if fissures.is_empty() {
+        return Ok(());
+    }
+    let email = email.ok_or_else(|| {
+        format!(
+            "the [email] section was removed from the config, discarding {} fissures",
+            fissures.len()
+        )
+    })?;
+    let subject = format!("Fissure Digest: {} Fissures", fissures.len());
+    email.send(&subject, digest_body(fissures)).await
+}
+
+#[async_trait]
+impl NotificationSink for EmailSink {
+    async fn notify(&self, event: &NotificationEvent, config: &Config) -> NotifyResult {
+        let email = config
+            .email
+            .as_ref()
+            .ok_or("the [email] section is missing from the config")?;
+        match (email.mode, event) {
+            (EmailMode::Immediate, _) => {
+                let (summary, body) = config.templates.render(event, OffsetDateTime::now_utc());
+                email.send(&summary, body).await
+            }
+            (EmailMode::Digest, NotificationEvent::NewFissures(fissures)) => {
+                self.collect(fissures, email);
+                Ok(())
+            }
+            (EmailMode::Digest, NotificationEvent::Expiring { .. }) => Ok(()),
+        }
+    }
+
+    /// Sends the digest right away instead of when it is due
+    async fn flush(&self, config: &Config) -> NotifyResult {
+        let fissures = self.digest.lock().unwrap().take(OffsetDateTime::now_utc());
+        send_digest(&fissures, config.email.as_ref()).await
+    }
+}
+
+/// A table of the fissures, soonest expiring first
+fn digest_body(fissures: &[Fissure]) -> String {
+    let time_format = time::format_description::parse("[hour]:[minute]").unwrap();
+    let mut fissures = fissures.iter().collect::<Vec<&Fissure>>();
+    fissures.sort_by_key(|fissure| fissure.expiry);
+    let mut rows = vec![[
+        "Tier",
+        "Mission",
+        "Node",
+        "Faction",
+        "Steel Path",
+        "Void Storm",
+        "Expires",
+    ]
+    .map(str::to_string)
+    .to_vec()];
+    for fissure in fissures {
+        rows.push(vec![
+            fissure.tier.to_string(),
+            fissure.mission_type.to_string(),
+            fissure.node.clone(),
+            fissure.enemy.to_string(),
+            if fissure.is_hard { "Yes" } else { "No" }.to_string(),
+            if fissure.is_storm { "Yes" } else { "No" }.to_string(),
+            fissure
+                .expiry
+                .to_offset(local_offset())
+                .format(&time_format)
+                .unwrap_or_default(),
+        ]);
+    }
+    format!("Fissures matching your filters:\n\n{}", table(rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{filters::Tier, test_util::MockSmtpServer};
+
+    fn now() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap()
+    }
+
+    fn fissure(id: &str, node: &str, minutes_left: i64) -> Fissure {
+        Fissure::sample(id, node, now() + time::Duration::minutes(minutes_left))
+    }
+
+    fn email(port: u16, mode: EmailMode) -> EmailConfig {
+        EmailConfig {
+            server: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: Security::None,
+            username: None,
+            password: Some("hunter2".to_string()),
+            from: "Reaper <reaper@example.com>".parse().unwrap(),
+            to: vec!["tenno@example.com".parse().unwrap()],
+            mode,
+            digest_minutes: 60,
+        }
+    }
+
+    #[test]
+    fn digest_body_lists_fissures_soonest_expiring_first() {
+        let mut later = fissure("f1", "Hydron (Sedna)", 50);
+        later.tier = Tier::Axi;
+        later.is_hard = true;
+        let body = digest_body(&[later, fissure("f2", "Mot (Void)", 20)]);
+        let lines = body.lines().map(str::trim_end).collect::<Vec<&str>>();
+        assert_eq!(
+            lines,
+            [
+                "Fissures matching your filters:",
+                "",
+                "Tier | Mission  | Node           | Faction | Steel Path | Void Storm | Expires |",
+                "Lith | Survival | Mot (Void)     | Grineer | No         | No         | 12:20   |",
+                "Axi  | Survival | Hydron (Sedna) | Grineer | Yes        | No         | 12:50   |",
+            ]
+        );
+    }
+
+    #[test]
+    fn digest_skips_duplicates_and_expired_fissures() {
+        let mut digest = Digest::default();
+        digest.add(&[
+            fissure("f1", "Hydron (Sedna)", 30),
+            fissure("f2", "Mot (Void)", 5),
+        ]);
+        digest.add(&[
+            fissure("f1", "Hydron (Sedna)", 30),
+            fissure("f3", "Ukko (Void)", 60),
+        ]);
+        digest.scheduled = true;
+
+        let ids = digest
+            .take(now() + time::Duration::minutes(10))
+            .into_iter()
+            .map(|fissure| fissure.id)
+            .collect::<Vec<String>>();
+        assert_eq!(ids, ["f1", "f3"]);
+        assert!(digest.fissures.is_empty());
+        assert!(!digest.scheduled);
+    }
+
+    #[test]
+    fn debug_leaves_out_the_password() {
+        let debug = format!("{:?}", email(25, EmailMode::Immediate));
+        assert!(debug.contains("<redacted>"));
+        assert!(!debug.contains("hunter2"));
+    }
+
+    #[tokio::test]
+    async fn sends_notifications_through_smtp() {
+        let server = MockSmtpServer::start().await;
+        let config = Config {
+            email: Some(email(server.port, EmailMode::Immediate)),
+            ..Config::default()
+        };
+        let sink = EmailSink::new(mpsc::channel(1).0, Arc::new(RwLock::new(config.clone())));
+        let event = NotificationEvent::NewFissures(vec![fissure("f1", "Hydron (Sedna)", 30)]);
+        sink.notify(&event, &config).await.unwrap();
+
+        let messages = server.messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("Subject: New Fissures"));
+        assert!(messages[0].contains("To: tenno@example.com"));
+        assert!(messages[0].contains("Lith Survival on Hydron (Sedna)"));
+    }
+
+    #[tokio::test]
+    async fn sends_digests_through_smtp() {
+        let server = MockSmtpServer::start().await;
+        let email = email(server.port, EmailMode::Digest);
+        send_digest(&[], Some(&email)).await.unwrap();
+        assert!(server.messages().is_empty());
+
+        send_digest(&[fissure("f1", "Hydron (Sedna)", 30)], Some(&email))
+            .await
+            .unwrap();
+        let messages = server.messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("Subject: Fissure Digest: 1 Fissures"));
+        assert!(messages[0].contains("Hydron (Sedna)"));
+
+        let fissures = [fissure("f1", "Hydron (Sedna)", 30)];
+        assert!(send_digest(&fissures, None).await.is_err());
+    }
+}
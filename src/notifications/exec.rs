@@ -0,0 +1,239 @@
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+use time::OffsetDateTime;
+use tokio::{
+    io::AsyncWriteExt,
+    process::Command,
+    sync::{mpsc, Semaphore},
+    task::JoinSet,
+};
+
+use super::{NotificationEvent, NotificationSink, NotifyResult};
+use crate::{config::Config, fissure_watcher, models::Fissure, template};
+
+/// Prefix of the environment variables describing the fissure
+const ENV_PREFIX: &str = "REAPERS_WF_";
+
+/// The command run by the "Exec" sink for every fissure of a notification event
+#[derive(Debug, Clone, Deserialize)]
+pub struct ExecConfig {
+    /// The program followed by its arguments
+    pub command: Vec<String>,
+    /// Seconds after which the command is killed
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// How many instances of the command may run at the same time
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+}
+
+fn default_timeout() -> u64 {
+    10
+}
+fn default_max_concurrent() -> usize {
+    4
+}
+
+/// Runs a command for every notified fissure, passing the fissure as JSON on stdin and as environment variables
+pub struct ExecSink {
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+    /// Limits the running commands, replaced when `max_concurrent` changes
+    limit: Mutex<(usize, Arc<Semaphore>)>,
+}
+
+impl ExecSink {
+    pub fn new(app_tx: mpsc::Sender<fissure_watcher::Event>) -> Self {
+        Self {
+            app_tx,
+            limit: Mutex::new((0, Arc::new(Semaphore::new(0)))),
+        }
+    }
+
+    fn semaphore(&self, max_concurrent: usize) -> Arc<Semaphore> {
+        let max_concurrent = max_concurrent.max(1);
+        let mut limit = self.limit.lock().unwrap();
+        if limit.0 != max_concurrent {
+            *limit = (max_concurrent, Arc::new(Semaphore::new(max_concurrent)));
+        }
+        Arc::clone(&limit.1)
+    }
+}
+
+#[async_trait]
+impl NotificationSink for ExecSink {
+    async fn notify(&self, event: &NotificationEvent, config: &Config) -> NotifyResult {
+        let exec = config
+            .exec
+            .as_ref()
+            .ok_or("the [exec] section is missing from the config")?;
+        if exec.command.is_empty() {
+            return Err("the exec command is empty".into());
+        }
+        let semaphore = self.semaphore(exec.max_concurrent);
+        let mut runs = JoinSet::new();
+        for fissure in event.fissures() {
+            let (exec, fissure, semaphore) =
+                (exec.clone(), fissure.clone(), Arc::clone(&semaphore));
+            let values = template::values(event, &fissure, OffsetDateTime::now_utc());
+            let app_tx = self.app_tx.clone();
+            runs.spawn(async move {
+                let _permit = semaphore.acquire_owned().await?;
+                run(&exec, &fissure, values, &app_tx).await
+            });
+        }
+        let mut result = Ok(());
+        while let Some(run) = runs.join_next().await {
+            if let Err(e) = run.map_err(|e| e.into()).and_then(|result| result) {
+                result = Err(e);
+            }
+        }
+        result
+    }
+}
+
+/// Runs the command for the fissure, forwarding each line it writes to stderr to the console log
+async fn run(
+    exec: &ExecConfig,
+    fissure: &Fissure,
+    values: HashMap<&'static str, String>,
+    app_tx: &mpsc::Sender<fissure_watcher::Event>,
+) -> NotifyResult {
+    let program = &exec.command[0];
+    let mut command = Command::new(program);
+    command
+        .args(&exec.command[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let input = serde_json::to_vec(&json!({ "event": values["event"], "fissure": fissure }))?;
+    for (name, value) in values {
+        command.env(format!("{}{}", ENV_PREFIX, name.to_uppercase()), value);
+    }
+    let mut child = command.spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    // the command may exit without reading its input, which is not an error
+    let _ = stdin.write_all(&input).await;
+    drop(stdin);
+    // dropping the future on timeout kills the command
+    let output = tokio::time::timeout(Duration::from_secs(exec.timeout), child.wait_with_output())
+        .await
+        .map_err(|_| format!("{} timed out after {}s", program, exec.timeout))??;
+    for line in String::from_utf8_lossy(&output.stderr).lines() {
+        if !line.trim().is_empty() {
+            let _ = app_tx
+                .send(fissure_watcher::Event::Log(format!(
+                    "{}: {}",
+                    program, line
+                )))
+                .await;
+        }
+    }
+    if !output.status.success() {
+        return Err(format!("{} failed with {}", program, output.status).into());
+    }
+    Ok(())
+}
+
+/// The tests run the commands with `sh`
+#[cfg(all(test, unix))]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn config(script: &str, timeout: u64, max_concurrent: usize) -> Config {
+        Config {
+            exec: Some(ExecConfig {
+                command: vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                timeout,
+                max_concurrent,
+            }),
+            ..Config::default()
+        }
+    }
+
+    fn event(ids: &[&str]) -> NotificationEvent {
+        let expiry = OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap();
+        NotificationEvent::NewFissures(
+            ids.iter()
+                .map(|id| Fissure::sample(id, "Hydron (Sedna)", expiry))
+                .collect(),
+        )
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("reapers-wf-exec-{}-{}", name, std::process::id()))
+    }
+
+    #[tokio::test]
+    async fn passes_the_fissure_in_the_environment_and_on_stdin() {
+        let (app_tx, mut app_rx) = mpsc::channel(8);
+        let config = config(
+            r#"echo "$REAPERS_WF_EVENT $REAPERS_WF_ID $REAPERS_WF_NODE" >&2; tr -d '\n' >&2"#,
+            5,
+            1,
+        );
+        ExecSink::new(app_tx)
+            .notify(&event(&["f1"]), &config)
+            .await
+            .unwrap();
+
+        let mut lines = Vec::new();
+        while let Ok(fissure_watcher::Event::Log(line)) = app_rx.try_recv() {
+            lines.push(line);
+        }
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert_eq!(lines[0], "sh: new f1 Hydron (Sedna)");
+        let input = serde_json::from_str::<serde_json::Value>(&lines[1]["sh: ".len()..]).unwrap();
+        assert_eq!(input["event"], "new");
+        assert_eq!(input["fissure"]["node"], "Hydron (Sedna)");
+    }
+
+    #[tokio::test]
+    async fn kills_commands_running_past_the_timeout() {
+        let pid_path = temp_path("pid");
+        let script = format!("echo $$ > {}; exec sleep 30", pid_path.display());
+        let start = std::time::Instant::now();
+        let error = ExecSink::new(mpsc::channel(1).0)
+            .notify(&event(&["f1"]), &config(&script, 1, 1))
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "sh timed out after 1s");
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        let pid = std::fs::read_to_string(&pid_path).unwrap();
+        std::fs::remove_file(&pid_path).unwrap();
+        let stat = PathBuf::from(format!("/proc/{}/stat", pid.trim()));
+        // killed, and either reaped already or a zombie waiting to be
+        let running = || {
+            std::fs::read_to_string(&stat)
+                .is_ok_and(|stat| stat.split_whitespace().nth(2) != Some("Z"))
+        };
+        let deadline = std::time::Instant::now() + Duration::from_secs(1);
+        while running() && std::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(!running(), "the command is still running");
+    }
+
+    #[tokio::test]
+    async fn runs_at_most_max_concurrent_commands() {
+        let lock = temp_path("lock");
+        // fails if another instance holds the lock
+        let script = format!("mkdir {0} || exit 1; sleep 0.2; rmdir {0}", lock.display());
+        ExecSink::new(mpsc::channel(8).0)
+            .notify(&event(&["f1", "f2", "f3"]), &config(&script, 5, 1))
+            .await
+            .unwrap();
+        assert!(!lock.exists());
+    }
+}
@@ -0,0 +1,351 @@
+use std::{collections::HashMap, error::Error, fmt::Display, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use procmacros::{Display, FromStr, Variants};
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tokio::{
+    sync::{mpsc, RwLock},
+    task::{JoinHandle, JoinSet},
+};
+
+use crate::{
+    config::Config,
+    fissure_watcher,
+    inbox::{self, Delivery, InboxEntry, Status},
+    models::Fissure,
+};
+use policy::{Disposition, Policy};
+
+mod desktop;
+mod discord;
+mod email;
+mod exec;
+mod policy;
+mod terminal;
+mod webhook;
+
+pub use desktop::Urgency;
+pub use email::EmailConfig;
+pub use exec::ExecConfig;
+pub use policy::PolicyConfig;
+pub use terminal::TerminalConfig;
+pub use webhook::WebhookConfig;
+
+pub type NotifyResult<T = ()> = Result<T, Box<dyn Error + Send + Sync>>;
+
+/// Something the user should be told about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationEvent {
+    /// New fissures matching the filters appeared
+    NewFissures(Vec<Fissure>),
+    /// A fissure the user was told about expires in `seconds` seconds
+    Expiring { fissure: Fissure, seconds: u64 },
+}
+
+impl NotificationEvent {
+    /// Name of the kind of event, as used in templates
+    pub fn kind(&self) -> &'static str {
+        match self {
+            NotificationEvent::NewFissures(_) => "new",
+            NotificationEvent::Expiring { .. } => "expiring",
+        }
+    }
+
+    /// The fissures the event is about
+    pub fn fissures(&self) -> &[Fissure] {
+        match self {
+            NotificationEvent::NewFissures(fissures) => fissures,
+            NotificationEvent::Expiring { fissure, .. } => std::slice::from_ref(fissure),
+        }
+    }
+}
+
+impl Display for NotificationEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NotificationEvent::NewFissures(fissures) => {
+                write!(f, "{} new fissures", fissures.len())
+            }
+            NotificationEvent::Expiring { fissure, seconds } => {
+                write!(f, "{} expiring in {}s", fissure, seconds)
+            }
+        }
+    }
+}
+
+/// Something the user clicked on a notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationAction {
+    /// Remind about the fissures again later
+    Snooze(Vec<Fissure>),
+    /// Stop showing and notifying fissures on the node
+    IgnoreNode(String),
+    /// Show the fissure in the app
+    Open(Fissure),
+}
+
+/// A backend that delivers notifications to the user
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    /// Delivers the event, the config is a snapshot taken when the event was dispatched
+    async fn notify(&self, event: &NotificationEvent, config: &Config) -> NotifyResult;
+
+    /// Delivers whatever the sink still holds back, called once before shutting down
+    async fn flush(&self, _config: &Config) -> NotifyResult {
+        Ok(())
+    }
+}
+
+/// The available notification backends
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Display, FromStr, Variants,
+)]
+pub enum SinkKind {
+    /// Desktop notifications through the operating system
+    Desktop,
+    /// Embeds posted to the Discord webhooks of the matching profiles
+    Discord,
+    /// Templated HTTP requests to the endpoints configured in `webhooks`
+    Webhook,
+    /// A command run with the fissure as JSON on stdin, configured in `[exec]`
+    Exec,
+    /// Bell, flashing tab bar and banner in the app's terminal, configured in `[terminal]`
+    Terminal,
+    /// Emails sent through the SMTP server configured in `[email]`, right away or as a digest
+    Email,
+}
+
+impl SinkKind {
+    fn create(
+        &self,
+        app_tx: &mpsc::Sender<fissure_watcher::Event>,
+        config: &Arc<RwLock<Config>>,
+    ) -> Arc<dyn NotificationSink> {
+        match self {
+            SinkKind::Desktop => Arc::new(desktop::DesktopSink::new(app_tx.clone())),
+            SinkKind::Discord => Arc::new(discord::DiscordSink::new()),
+            SinkKind::Webhook => Arc::new(webhook::WebhookSink::new(app_tx.clone())),
+            SinkKind::Exec => Arc::new(exec::ExecSink::new(app_tx.clone())),
+            SinkKind::Terminal => Arc::new(terminal::TerminalSink::new(app_tx.clone())),
+            SinkKind::Email => Arc::new(email::EmailSink::new(app_tx.clone(), Arc::clone(config))),
+        }
+    }
+}
+
+/// Spawns the task that delivers notification events to the sinks enabled in the config,
+/// reporting failed deliveries to the app instead of panicking.
+/// Events pass through the notification policy of the config, which decides when they are delivered.
+/// Every sent or suppressed event is recorded in the inbox.
+/// # Returns
+/// The sender to dispatch events with, and the handle of the task.
+pub fn spawn(
+    config: Arc<RwLock<Config>>,
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+) -> (mpsc::Sender<NotificationEvent>, JoinHandle<()>) {
+    let (tx, mut rx) = mpsc::channel::<NotificationEvent>(32);
+    let handle = tokio::spawn(async move {
+        let sinks = SinkKind::VARIANTS
+            .iter()
+            .map(|kind| (*kind, kind.create(&app_tx, &config)))
+            .collect::<HashMap<SinkKind, Arc<dyn NotificationSink>>>();
+        let mut policy = Policy::default();
+        let (entries, message) = match inbox::load().await {
+            Ok((entries, 0)) => (entries, None),
+            Ok((entries, skipped)) => (
+                entries,
+                Some(format!(
+                    "Skipped {} unreadable notifications in {}",
+                    skipped,
+                    inbox::INBOX_PATH
+                )),
+            ),
+            Err(e) => (
+                Vec::new(),
+                Some(format!("Failed to load {}: {}", inbox::INBOX_PATH, e)),
+            ),
+        };
+        if let Some(message) = message {
+            let _ = app_tx.send(fissure_watcher::Event::Err(message)).await;
+        }
+        if app_tx
+            .send(fissure_watcher::Event::Inbox(entries))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        loop {
+            let snapshot = config.read().await.clone();
+            let now = OffsetDateTime::now_utc();
+            let wakeup = policy
+                .next_wakeup(&snapshot, now)
+                .map(|wakeup| Duration::try_from(wakeup - now).unwrap_or_default());
+            tokio::select! {
+                event = rx.recv() => {
+                    let Some(event) = event else {
+                        flush(&mut policy, &sinks, &config, &app_tx).await;
+                        return;
+                    };
+                    for (event, disposition) in policy.push(event, &snapshot, now) {
+                        let (status, message) = match disposition {
+                            Disposition::Queued => continue,
+                            Disposition::Held => (Status::Held, format!("Holding back until quiet hours end: {}", event)),
+                            Disposition::Dropped => (Status::Dropped, format!("Dropped during quiet hours: {}", event)),
+                        };
+                        if app_tx.send(fissure_watcher::Event::Log(message)).await.is_err() {
+                            return;
+                        }
+                        let entry = InboxEntry {
+                            time: now,
+                            event: event.kind().to_string(),
+                            status,
+                            deliveries: Vec::new(),
+                            fissures: event.fissures().to_vec(),
+                        };
+                        if !record(entry, &app_tx).await {
+                            return;
+                        }
+                    }
+                }
+                _ = tokio::time::sleep(wakeup.unwrap_or_default()), if wakeup.is_some() => {}
+            }
+            let config = Arc::new(config.read().await.clone());
+            for event in policy.poll(&config, OffsetDateTime::now_utc()) {
+                if !deliver(&sinks, event, &config, &app_tx).await {
+                    return;
+                }
+            }
+        }
+    });
+    (tx, handle)
+}
+
+/// Delivers the events the policy still holds and flushes the sinks, done once the channel was closed for shutting
+/// down. Events that had to be discarded are logged and recorded in the inbox.
+async fn flush(
+    policy: &mut Policy,
+    sinks: &HashMap<SinkKind, Arc<dyn NotificationSink>>,
+    config: &Arc<RwLock<Config>>,
+    app_tx: &mpsc::Sender<fissure_watcher::Event>,
+) {
+    let config = Arc::new(config.read().await.clone());
+    let now = OffsetDateTime::now_utc();
+    let (ready, discarded) = policy.flush(&config, now);
+    for event in discarded {
+        let message = format!("Discarded when shutting down: {}", event);
+        if app_tx
+            .send(fissure_watcher::Event::Log(message))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        let entry = InboxEntry {
+            time: now,
+            event: event.kind().to_string(),
+            status: Status::Dropped,
+            deliveries: Vec::new(),
+            fissures: event.fissures().to_vec(),
+        };
+        if !record(entry, app_tx).await {
+            return;
+        }
+    }
+    for event in ready {
+        if !deliver(sinks, event, &config, app_tx).await {
+            return;
+        }
+    }
+    let mut flushes = JoinSet::new();
+    for (kind, sink) in sinks {
+        let (kind, sink, config) = (*kind, Arc::clone(sink), Arc::clone(&config));
+        flushes.spawn(async move { (kind, sink.flush(&config).await) });
+    }
+    while let Some(flush) = flushes.join_next().await {
+        let message = match flush {
+            Ok((_, Ok(()))) => continue,
+            Ok((sink, Err(e))) => format!("{} notification failed: {}", sink, e),
+            Err(e) => format!("Notification task failed: {}", e),
+        };
+        if app_tx
+            .send(fissure_watcher::Event::Err(message))
+            .await
+            .is_err()
+        {
+            return;
+        }
+    }
+}
+
+/// Delivers the event to every sink enabled in the config at the same time and records the outcome in the inbox.
+/// # Returns
+/// `false` if the app is gone.
+async fn deliver(
+    sinks: &HashMap<SinkKind, Arc<dyn NotificationSink>>,
+    event: NotificationEvent,
+    config: &Arc<Config>,
+    app_tx: &mpsc::Sender<fissure_watcher::Event>,
+) -> bool {
+    let event = Arc::new(event);
+    let mut entry = InboxEntry {
+        time: OffsetDateTime::now_utc(),
+        event: event.kind().to_string(),
+        status: Status::Sent,
+        deliveries: Vec::new(),
+        fissures: event.fissures().to_vec(),
+    };
+    let mut deliveries = JoinSet::new();
+    for kind in &config.notification_sinks {
+        let sink = Arc::clone(&sinks[kind]);
+        let (kind, event, config) = (*kind, Arc::clone(&event), Arc::clone(config));
+        deliveries.spawn(async move { (kind, sink.notify(&event, &config).await) });
+    }
+    while let Some(delivery) = deliveries.join_next().await {
+        let message = match delivery {
+            Ok((sink, Ok(()))) => {
+                entry.deliveries.push(Delivery { sink, error: None });
+                continue;
+            }
+            Ok((sink, Err(e))) => {
+                entry.deliveries.push(Delivery {
+                    sink,
+                    error: Some(e.to_string()),
+                });
+                format!("{} notification failed: {}", sink, e)
+            }
+            Err(e) => format!("Notification task failed: {}", e),
+        };
+        if app_tx
+            .send(fissure_watcher::Event::Err(message))
+            .await
+            .is_err()
+        {
+            return false;
+        }
+    }
+    entry
+        .deliveries
+        .sort_by_key(|delivery| delivery.sink.to_string());
+    record(entry, app_tx).await
+}
+
+/// Appends the entry to the inbox file and shows it in the app.
+/// # Returns
+/// `false` if the app is gone.
+async fn record(entry: InboxEntry, app_tx: &mpsc::Sender<fissure_watcher::Event>) -> bool {
+    if let Err(e) = inbox::append(&entry).await {
+        let message = format!("Failed to write {}: {}", inbox::INBOX_PATH, e);
+        if app_tx
+            .send(fissure_watcher::Event::Err(message))
+            .await
+            .is_err()
+        {
+            return false;
+        }
+    }
+    app_tx
+        .send(fissure_watcher::Event::Notification(entry))
+        .await
+        .is_ok()
+}
@@ -0,0 +1,516 @@
+use std::collections::{HashMap, VecDeque};
+
+use procmacros::Display;
+use serde::{Deserialize, Deserializer};
+use time::{Duration, OffsetDateTime, Time, Weekday};
+
+use super::NotificationEvent;
+use crate::{config::Config, models::Fissure, util::local_offset};
+
+/// How often held back events are checked while waiting for quiet hours to end
+const QUIET_CHECK_INTERVAL: Duration = Duration::MINUTE;
+
+/// What happens to events that arrive during quiet hours
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Display)]
+pub enum QuietMode {
+    /// Hold the events back and send a summary once quiet hours are over
+    Hold,
+    /// Discard the events
+    Drop,
+}
+
+/// A local time range during which no notifications are sent, wrapping around midnight if `end` is before `start`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct QuietHours {
+    #[serde(deserialize_with = "deserialize_time")]
+    pub start: Time,
+    #[serde(deserialize_with = "deserialize_time")]
+    pub end: Time,
+    /// Days on which the range starts, every day if empty
+    #[serde(default, deserialize_with = "deserialize_weekdays")]
+    pub days: Vec<Weekday>,
+}
+
+impl QuietHours {
+    fn contains(&self, local: OffsetDateTime) -> bool {
+        let on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        let time = local.time();
+        if self.start <= self.end {
+            on(local.weekday()) && self.start <= time && time < self.end
+        } else {
+            // the part after midnight belongs to the range that started the day before
+            (on(local.weekday()) && time >= self.start)
+                || (on(local.weekday().previous()) && time < self.end)
+        }
+    }
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Time, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    let format = time::format_description::parse("[hour]:[minute]").unwrap();
+    Time::parse(&text, &format)
+        .map_err(|_| serde::de::Error::custom(format!("Invalid time \"{}\", expected HH:MM", text)))
+}
+
+fn deserialize_weekdays<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Weekday>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|day| {
+            let weekday = match day.to_lowercase().get(..3) {
+                Some("mon") => Weekday::Monday,
+                Some("tue") => Weekday::Tuesday,
+                Some("wed") => Weekday::Wednesday,
+                Some("thu") => Weekday::Thursday,
+                Some("fri") => Weekday::Friday,
+                Some("sat") => Weekday::Saturday,
+                Some("sun") => Weekday::Sunday,
+                _ => {
+                    return Err(serde::de::Error::custom(format!(
+                        "Invalid weekday \"{}\", expected e.g. \"Mon\" or \"Monday\"",
+                        day
+                    )))
+                }
+            };
+            Ok(weekday)
+        })
+        .collect()
+}
+
+/// Limits how often notifications are sent
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PolicyConfig {
+    /// Seconds to wait for further events after the first one, which are then sent together. 0 sends events immediately
+    #[serde(default)]
+    pub coalesce_window: u64,
+    /// Most notifications per hour, further events are sent together once possible. 0 means no limit
+    #[serde(default)]
+    pub max_per_hour: usize,
+    #[serde(default)]
+    pub quiet_hours: Vec<QuietHours>,
+    #[serde(default = "default_quiet_mode")]
+    pub quiet_mode: QuietMode,
+}
+
+fn default_quiet_mode() -> QuietMode {
+    QuietMode::Hold
+}
+
+impl Default for PolicyConfig {
+    fn default() -> Self {
+        Self {
+            coalesce_window: 0,
+            max_per_hour: 0,
+            quiet_hours: Vec::new(),
+            quiet_mode: default_quiet_mode(),
+        }
+    }
+}
+
+impl PolicyConfig {
+    pub fn is_quiet(&self, now: OffsetDateTime) -> bool {
+        let local = now.to_offset(local_offset());
+        self.quiet_hours.iter().any(|hours| hours.contains(local))
+    }
+}
+
+/// What the policy did with an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposition {
+    /// Will be sent once the coalescing window is over
+    Queued,
+    /// Held back until quiet hours are over
+    Held,
+    Dropped,
+}
+
+/// Decides when events are delivered to the sinks, driven by the time passed in so that it does not depend on the clock
+#[derive(Default)]
+pub struct Policy {
+    /// Events waiting to be sent
+    pending: Vec<NotificationEvent>,
+    /// When the pending events are sent, unless the hourly limit is reached
+    deadline: Option<OffsetDateTime>,
+    /// Events that arrived during quiet hours
+    held: Vec<NotificationEvent>,
+    /// When the notifications of the last hour were sent, oldest first
+    sent: VecDeque<OffsetDateTime>,
+}
+
+impl Policy {
+    /// Queues the event, or holds back or drops it during quiet hours. Urgent fissures of an event with several
+    /// fissures are split off and queued, so that they are not held back with the rest.
+    /// # Returns
+    /// The parts of the event and what happened to each.
+    pub fn push(
+        &mut self,
+        event: NotificationEvent,
+        config: &Config,
+        now: OffsetDateTime,
+    ) -> Vec<(NotificationEvent, Disposition)> {
+        let policy = &config.notification_policy;
+        if !policy.is_quiet(now) {
+            return vec![(self.queue(event, config, now), Disposition::Queued)];
+        }
+        let (urgent, other) = match event {
+            NotificationEvent::NewFissures(fissures) => {
+                let (urgent, other) = fissures
+                    .into_iter()
+                    .partition::<Vec<Fissure>, _>(|fissure| is_urgent(fissure, config));
+                (
+                    (!urgent.is_empty()).then_some(NotificationEvent::NewFissures(urgent)),
+                    (!other.is_empty()).then_some(NotificationEvent::NewFissures(other)),
+                )
+            }
+            event @ NotificationEvent::Expiring { .. }
+                if is_urgent(&event.fissures()[0], config) =>
+            {
+                (Some(event), None)
+            }
+            event @ NotificationEvent::Expiring { .. } => (None, Some(event)),
+        };
+        let mut parts = Vec::new();
+        if let Some(event) = urgent {
+            parts.push((self.queue(event, config, now), Disposition::Queued));
+        }
+        if let Some(event) = other {
+            match policy.quiet_mode {
+                QuietMode::Hold => {
+                    self.held.push(event.clone());
+                    parts.push((event, Disposition::Held));
+                }
+                QuietMode::Drop => parts.push((event, Disposition::Dropped)),
+            }
+        }
+        parts
+    }
+
+    /// Adds the event to the pending ones, starting the coalescing window if none is running
+    fn queue(
+        &mut self,
+        event: NotificationEvent,
+        config: &Config,
+        now: OffsetDateTime,
+    ) -> NotificationEvent {
+        let window = config.notification_policy.coalesce_window as i64;
+        self.pending.push(event.clone());
+        self.deadline.get_or_insert(now + Duration::seconds(window));
+        event
+    }
+
+    /// Removes and returns the events that should be delivered now
+    pub fn poll(&mut self, config: &Config, now: OffsetDateTime) -> Vec<NotificationEvent> {
+        let policy = &config.notification_policy;
+        if !self.held.is_empty() && !policy.is_quiet(now) {
+            let held = summarise(std::mem::take(&mut self.held), now);
+            if !held.is_empty() {
+                self.pending.extend(held);
+                self.deadline.get_or_insert(now);
+            }
+        }
+        if self.pending.is_empty() || self.deadline.is_some_and(|deadline| deadline > now) {
+            return Vec::new();
+        }
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| *sent + Duration::HOUR <= now)
+        {
+            self.sent.pop_front();
+        }
+        let mut ready = coalesce(std::mem::take(&mut self.pending), now);
+        let available = match policy.max_per_hour {
+            0 => ready.len(),
+            max => max.saturating_sub(self.sent.len()),
+        };
+        // events over the limit are merged with later ones once a notification is available again
+        self.pending = ready.split_off(available.min(ready.len()));
+        if self.pending.is_empty() {
+            self.deadline = None;
+        }
+        self.sent.extend(ready.iter().map(|_| now));
+        ready
+    }
+
+    /// Empties the policy before shutting down: the pending events are sent without waiting for the coalescing
+    /// window, as are the held back ones if quiet hours are over. Events over the hourly limit and events held
+    /// back during quiet hours are discarded.
+    /// # Returns
+    /// The events to send and the discarded ones.
+    pub fn flush(
+        &mut self,
+        config: &Config,
+        now: OffsetDateTime,
+    ) -> (Vec<NotificationEvent>, Vec<NotificationEvent>) {
+        let mut discarded = Vec::new();
+        if config.notification_policy.is_quiet(now) {
+            discarded = std::mem::take(&mut self.held);
+        }
+        self.deadline = Some(now);
+        let ready = self.poll(config, now);
+        discarded.append(&mut self.pending);
+        self.deadline = None;
+        (ready, discarded)
+    }
+
+    /// When `poll` should be called next, `None` if nothing is waiting
+    pub fn next_wakeup(&self, config: &Config, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        let limit = config.notification_policy.max_per_hour;
+        let mut wakeups = Vec::new();
+        if !self.pending.is_empty() {
+            let deadline = self.deadline.unwrap_or(now);
+            if limit > 0 && self.sent.len() >= limit {
+                let available_at = self.sent[self.sent.len() - limit] + Duration::HOUR;
+                wakeups.push(deadline.max(available_at));
+            } else {
+                wakeups.push(deadline);
+            }
+        }
+        if !self.held.is_empty() {
+            wakeups.push(now + QUIET_CHECK_INTERVAL);
+        }
+        wakeups.into_iter().min()
+    }
+}
+
+/// Whether the fissure matches a profile that bypasses quiet hours
+fn is_urgent(fissure: &Fissure, config: &Config) -> bool {
+    config
+        .matching_profiles(fissure)
+        .iter()
+        .any(|profile| profile.urgent)
+}
+
+/// Merges all new fissures into one event, keeping one reminder per fissure and dropping those for expired fissures
+fn coalesce(events: Vec<NotificationEvent>, now: OffsetDateTime) -> Vec<NotificationEvent> {
+    let mut new = Vec::<Fissure>::new();
+    let mut expiring = HashMap::<String, NotificationEvent>::new();
+    for event in events {
+        match event {
+            NotificationEvent::NewFissures(fissures) => {
+                for fissure in fissures {
+                    if !new.iter().any(|known| known.id == fissure.id) {
+                        new.push(fissure);
+                    }
+                }
+            }
+            NotificationEvent::Expiring { ref fissure, .. } => {
+                if fissure.expiry > now {
+                    expiring.insert(fissure.id.clone(), event);
+                }
+            }
+        }
+    }
+    let mut expiring = expiring.into_values().collect::<Vec<NotificationEvent>>();
+    expiring.sort_by_key(|event| event.fissures()[0].expiry);
+    let mut events = Vec::new();
+    if !new.is_empty() {
+        events.push(NotificationEvent::NewFissures(new));
+    }
+    events.extend(expiring);
+    events
+}
+
+/// The held back events that are still relevant: the new fissures that have not expired yet in one event,
+/// and the reminders of fissures that have not expired, with the seconds left updated
+fn summarise(events: Vec<NotificationEvent>, now: OffsetDateTime) -> Vec<NotificationEvent> {
+    coalesce(events, now)
+        .into_iter()
+        .filter_map(|event| match event {
+            NotificationEvent::NewFissures(mut fissures) => {
+                fissures.retain(|fissure| fissure.expiry > now);
+                (!fissures.is_empty()).then_some(NotificationEvent::NewFissures(fissures))
+            }
+            NotificationEvent::Expiring { fissure, .. } => Some(NotificationEvent::Expiring {
+                seconds: (fissure.expiry - now).whole_seconds().max(0) as u64,
+                fissure,
+            }),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mission_type::MissionType;
+
+    fn now() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap()
+    }
+
+    fn time(hour: u8) -> Time {
+        Time::from_hms(hour, 0, 0).unwrap()
+    }
+
+    /// Quiet all day, urgent for survival fissures
+    fn config(quiet: bool) -> Config {
+        let mut config = Config::default();
+        config.default_profile.urgent = true;
+        config.default_profile.filter.mission_filter = vec![MissionType::Survival];
+        if quiet {
+            config.notification_policy.quiet_hours = vec![
+                QuietHours {
+                    start: time(0),
+                    end: time(12),
+                    days: Vec::new(),
+                },
+                QuietHours {
+                    start: time(12),
+                    end: time(0),
+                    days: Vec::new(),
+                },
+            ];
+        }
+        config
+    }
+
+    fn fissure(id: &str, mission_type: MissionType, minutes_left: i64) -> Fissure {
+        let mut fissure = Fissure::sample(
+            id,
+            "Hydron (Sedna)",
+            now() + Duration::minutes(minutes_left),
+        );
+        fissure.mission_type = mission_type;
+        fissure
+    }
+
+    fn ids(event: &NotificationEvent) -> Vec<&str> {
+        event
+            .fissures()
+            .iter()
+            .map(|fissure| fissure.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn queues_urgent_fissures_and_holds_the_rest() {
+        let config = config(true);
+        let mut policy = Policy::default();
+        let event = NotificationEvent::NewFissures(vec![
+            fissure("urgent", MissionType::Survival, 30),
+            fissure("other", MissionType::Capture, 30),
+        ]);
+
+        let parts = policy.push(event, &config, now());
+        assert_eq!(parts.len(), 2);
+        assert_eq!(
+            (ids(&parts[0].0), parts[0].1),
+            (vec!["urgent"], Disposition::Queued)
+        );
+        assert_eq!(
+            (ids(&parts[1].0), parts[1].1),
+            (vec!["other"], Disposition::Held)
+        );
+
+        let sent = policy.poll(&config, now());
+        assert_eq!(sent.len(), 1);
+        assert_eq!(ids(&sent[0]), ["urgent"]);
+    }
+
+    #[test]
+    fn flush_sends_pending_events_and_discards_held_ones() {
+        let mut config = config(true);
+        config.notification_policy.coalesce_window = 60;
+        let mut policy = Policy::default();
+        policy.push(
+            NotificationEvent::NewFissures(vec![
+                fissure("urgent", MissionType::Survival, 30),
+                fissure("other", MissionType::Capture, 30),
+            ]),
+            &config,
+            now(),
+        );
+        assert!(policy.poll(&config, now()).is_empty());
+
+        let (ready, discarded) = policy.flush(&config, now());
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ids(&ready[0]), ["urgent"]);
+        assert_eq!(discarded.len(), 1);
+        assert_eq!(ids(&discarded[0]), ["other"]);
+        assert_eq!(policy.next_wakeup(&config, now()), None);
+    }
+
+    #[test]
+    fn events_within_the_coalescing_window_are_sent_together() {
+        let mut config = config(false);
+        config.notification_policy.coalesce_window = 60;
+        let mut policy = Policy::default();
+        let new = |id| NotificationEvent::NewFissures(vec![fissure(id, MissionType::Survival, 30)]);
+
+        policy.push(new("a"), &config, now());
+        policy.push(new("b"), &config, now() + Duration::seconds(30));
+        // the window started with the first event and is not extended by the second
+        let deadline = now() + Duration::seconds(60);
+        assert_eq!(policy.next_wakeup(&config, now()), Some(deadline));
+        assert!(policy.poll(&config, deadline - Duration::SECOND).is_empty());
+
+        let sent = policy.poll(&config, deadline);
+        assert_eq!(sent.len(), 1);
+        assert_eq!(ids(&sent[0]), ["a", "b"]);
+        assert_eq!(policy.next_wakeup(&config, deadline), None);
+    }
+
+    #[test]
+    fn events_over_the_hourly_limit_wait_until_a_notification_is_available() {
+        let mut config = config(false);
+        config.notification_policy.max_per_hour = 2;
+        let mut policy = Policy::default();
+        let new = |id| NotificationEvent::NewFissures(vec![fissure(id, MissionType::Survival, 90)]);
+        let at = |minutes| now() + Duration::minutes(minutes);
+
+        for (id, minutes) in [("a", 0), ("b", 10)] {
+            policy.push(new(id), &config, at(minutes));
+            let sent = policy.poll(&config, at(minutes));
+            assert_eq!(sent.len(), 1);
+            assert_eq!(ids(&sent[0]), [id]);
+        }
+
+        // the third and fourth event of the hour are held back until the first notification is an hour old
+        policy.push(new("c"), &config, at(20));
+        assert!(policy.poll(&config, at(20)).is_empty());
+        assert_eq!(policy.next_wakeup(&config, at(20)), Some(at(60)));
+        policy.push(new("d"), &config, at(30));
+        assert!(policy.poll(&config, at(30)).is_empty());
+        assert_eq!(policy.next_wakeup(&config, at(30)), Some(at(60)));
+        assert!(policy.poll(&config, at(59)).is_empty());
+
+        let sent = policy.poll(&config, at(60));
+        assert_eq!(sent.len(), 1);
+        assert_eq!(ids(&sent[0]), ["c", "d"]);
+        // the limit is reached again until the second notification is an hour old
+        policy.push(new("e"), &config, at(61));
+        assert!(policy.poll(&config, at(61)).is_empty());
+        assert_eq!(policy.next_wakeup(&config, at(61)), Some(at(70)));
+    }
+
+    #[test]
+    fn summary_keeps_reminders_of_open_fissures() {
+        let mut policy = Policy::default();
+        let expiring = |fissure| NotificationEvent::Expiring {
+            fissure,
+            seconds: 300,
+        };
+        for event in [
+            NotificationEvent::NewFissures(vec![
+                fissure("new", MissionType::Capture, 60),
+                fissure("closed", MissionType::Capture, 10),
+            ]),
+            expiring(fissure("open", MissionType::Capture, 30)),
+            expiring(fissure("expired", MissionType::Capture, 5)),
+        ] {
+            policy.push(event, &config(true), now());
+        }
+
+        let later = now() + Duration::minutes(20);
+        let sent = policy.poll(&config(false), later);
+        assert_eq!(sent.len(), 2);
+        assert!(matches!(sent[0], NotificationEvent::NewFissures(_)));
+        assert_eq!(ids(&sent[0]), ["new"]);
+        assert!(matches!(
+            sent[1],
+            NotificationEvent::Expiring { seconds: 600, .. }
+        ));
+        assert_eq!(ids(&sent[1]), ["open"]);
+    }
+}
@@ -0,0 +1,66 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use super::{NotificationEvent, NotificationSink, NotifyResult};
+use crate::{config::Config, fissure_watcher};
+
+/// How the "Terminal" sink alerts inside the app's terminal
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TerminalConfig {
+    /// Ring the terminal bell
+    #[serde(default = "default_true")]
+    pub bell: bool,
+    /// Seconds the tab bar flashes for, 0 disables flashing
+    #[serde(default = "default_flash_seconds")]
+    pub flash_seconds: u64,
+    /// Seconds the banner listing the fissures is shown for, 0 disables the banner
+    #[serde(default = "default_banner_seconds")]
+    pub banner_seconds: u64,
+    /// Show the number of unacknowledged matching fissures in the terminal or tmux window title,
+    /// independent of whether the sink is enabled
+    #[serde(default)]
+    pub window_title: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+fn default_flash_seconds() -> u64 {
+    3
+}
+fn default_banner_seconds() -> u64 {
+    10
+}
+
+impl Default for TerminalConfig {
+    fn default() -> Self {
+        Self {
+            bell: true,
+            flash_seconds: default_flash_seconds(),
+            banner_seconds: default_banner_seconds(),
+            window_title: false,
+        }
+    }
+}
+
+/// Alerts in the app itself, for setups where desktop notifications do not arrive, e.g. over SSH or in tmux
+pub struct TerminalSink {
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+}
+
+impl TerminalSink {
+    pub fn new(app_tx: mpsc::Sender<fissure_watcher::Event>) -> Self {
+        Self { app_tx }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for TerminalSink {
+    async fn notify(&self, event: &NotificationEvent, _config: &Config) -> NotifyResult {
+        self.app_tx
+            .send(fissure_watcher::Event::Alert(event.clone()))
+            .await
+            .map_err(|_| "the app is not running".into())
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use reqwest::Method;
+use serde::{Deserialize, Deserializer, Serialize};
+use time::OffsetDateTime;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc, watch},
+    task::JoinSet,
+};
+
+use super::{NotificationEvent, NotificationSink, NotifyResult};
+use crate::{
+    config::Config,
+    fissure_watcher,
+    template::{self, Escape, Template},
+};
+
+pub const DEAD_LETTER_PATH: &str = "reapers-wf-dead-letters.jsonl";
+/// Wait before the first retry, doubled for every further retry
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// An HTTP request sent for every fissure of a notification event
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookConfig {
+    pub url: Template,
+    #[serde(default = "default_method", deserialize_with = "deserialize_method")]
+    pub method: Method,
+    #[serde(default)]
+    pub headers: BTreeMap<String, Template>,
+    /// No body is sent if not set
+    #[serde(default)]
+    pub body: Option<Template>,
+    /// How often to retry a failed request before giving up
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_method() -> Method {
+    Method::POST
+}
+fn deserialize_method<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Method, D::Error> {
+    let method = String::deserialize(deserializer)?;
+    Method::from_bytes(method.to_uppercase().as_bytes()).map_err(serde::de::Error::custom)
+}
+fn default_retries() -> u32 {
+    3
+}
+
+/// A request that could not be delivered, appended to the dead-letter log
+#[derive(Serialize)]
+struct DeadLetter {
+    #[serde(with = "time::serde::iso8601")]
+    time: OffsetDateTime,
+    method: String,
+    url: String,
+    body: Option<String>,
+    attempts: u32,
+    error: String,
+}
+
+/// A webhook request rendered for a fissure
+#[derive(Debug, Clone)]
+struct Request {
+    method: Method,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+}
+
+impl Request {
+    /// Renders the templates of the webhook. Values are percent-encoded in the URL and JSON-escaped in JSON bodies,
+    /// unless the template says otherwise
+    fn new(webhook: &WebhookConfig, values: &HashMap<&str, String>) -> Self {
+        let headers = webhook
+            .headers
+            .iter()
+            .map(|(name, value)| (name.clone(), value.render(values)))
+            .collect::<Vec<(String, String)>>();
+        let json = headers.iter().any(|(name, value)| {
+            name.eq_ignore_ascii_case("content-type") && value.to_lowercase().contains("json")
+        });
+        let escape = if json { Escape::Json } else { Escape::Raw };
+        Self {
+            method: webhook.method.clone(),
+            url: webhook.url.render_escaped(values, Escape::Url),
+            headers,
+            body: webhook
+                .body
+                .as_ref()
+                .map(|body| body.render_escaped(values, escape)),
+        }
+    }
+
+    /// Sends the request once.
+    /// # Returns
+    /// On failure the error, and whether retrying could help, which is the case for connection errors,
+    /// server errors and rate limiting.
+    async fn send(&self, client: &reqwest::Client) -> Result<(), (String, bool)> {
+        let mut request = client.request(self.method.clone(), &self.url);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(body) = &self.body {
+            request = request.body(body.clone());
+        }
+        match request.send().await {
+            Ok(response) if response.status().is_success() => Ok(()),
+            Ok(response) => Err((
+                format!("{} responded with {}", self.url, response.status()),
+                response.status().is_server_error()
+                    || response.status() == reqwest::StatusCode::TOO_MANY_REQUESTS,
+            )),
+            Err(e) => Err((e.to_string(), !e.is_builder())),
+        }
+    }
+
+    /// Writes the request to the dead-letter log.
+    /// # Returns
+    /// The error to report.
+    async fn give_up(self, dead_letters: &Path, attempts: u32, error: String) -> String {
+        let letter = DeadLetter {
+            time: OffsetDateTime::now_utc(),
+            method: self.method.to_string(),
+            url: self.url,
+            body: self.body,
+            attempts,
+            error: error.clone(),
+        };
+        match write_dead_letter(dead_letters, &letter).await {
+            Ok(()) => error,
+            Err(e) => format!("{}, writing the dead letter failed: {}", error, e),
+        }
+    }
+}
+
+/// Sends templated HTTP requests to the webhooks configured in `webhooks`
+pub struct WebhookSink {
+    client: reqwest::Client,
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+    /// The requests being retried in the background, aborted when the sink is dropped
+    retries: Mutex<JoinSet<()>>,
+    /// Set when shutting down, so that the retries make their last attempt without waiting
+    stop: watch::Sender<bool>,
+    /// Where requests that could not be delivered are logged
+    dead_letters: PathBuf,
+}
+
+impl WebhookSink {
+    pub fn new(app_tx: mpsc::Sender<fissure_watcher::Event>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            app_tx,
+            retries: Mutex::new(JoinSet::new()),
+            stop: watch::channel(false).0,
+            dead_letters: PathBuf::from(DEAD_LETTER_PATH),
+        }
+    }
+
+    /// Sends the request once. If that fails and retrying could help, the retries run in the background,
+    /// so that a failing webhook does not hold up the following notifications, and only a request that is
+    /// given up on is reported as a failure.
+    async fn deliver(
+        &self,
+        webhook: &WebhookConfig,
+        values: &HashMap<&str, String>,
+    ) -> NotifyResult {
+        let request = Request::new(webhook, values);
+        match request.send(&self.client).await {
+            Ok(()) => Ok(()),
+            Err((error, true)) if webhook.retries > 0 => {
+                let (client, app_tx) = (self.client.clone(), self.app_tx.clone());
+                let (webhook_retries, dead_letters) = (webhook.retries, self.dead_letters.clone());
+                let mut retries = self.retries.lock().unwrap();
+                while retries.try_join_next().is_some() {}
+                let stop = self.stop.subscribe();
+                let message = format!(
+                    "Webhook request failed: {}, retrying in the background",
+                    error
+                );
+                retries.spawn(async move {
+                    let _ = app_tx.send(fissure_watcher::Event::Log(message)).await;
+                    retry(client, request, webhook_retries, dead_letters, app_tx, stop).await;
+                });
+                Ok(())
+            }
+            Err((error, _)) => Err(request.give_up(&self.dead_letters, 1, error).await.into()),
+        }
+    }
+}
+
+/// Retries the request with exponential backoff, reporting the outcome to the app.
+/// Once `stop` is set, the next attempt is made right away and is the last one.
+/// Requests that still fail are written to the dead-letter log.
+async fn retry(
+    client: reqwest::Client,
+    request: Request,
+    retries: u32,
+    dead_letters: PathBuf,
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempts = 1;
+    let mut stopping = *stop.borrow();
+    let result = loop {
+        if !stopping {
+            tokio::select! {
+                _ = tokio::time::sleep(backoff) => {}
+                Ok(_) = stop.wait_for(|stop| *stop) => stopping = true,
+            }
+        }
+        backoff *= 2;
+        attempts += 1;
+        match request.send(&client).await {
+            Ok(()) => break Ok(()),
+            Err((error, retry)) if !retry || stopping || attempts > retries => break Err(error),
+            Err(_) => {}
+        }
+    };
+    let event = match result {
+        Ok(()) => fissure_watcher::Event::Log(format!(
+            "Webhook request to {} succeeded after {} attempts",
+            request.url, attempts
+        )),
+        Err(error) => {
+            let error = request.give_up(&dead_letters, attempts, error).await;
+            fissure_watcher::Event::Err(format!(
+                "Webhook request failed after {} attempts: {}",
+                attempts, error
+            ))
+        }
+    };
+    let _ = app_tx.send(event).await;
+}
+
+async fn write_dead_letter(path: &Path, letter: &DeadLetter) -> NotifyResult {
+    let mut line = serde_json::to_string(letter)?;
+    line.push('\n');
+    tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?
+        .write_all(line.as_bytes())
+        .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    async fn notify(&self, event: &NotificationEvent, config: &Config) -> NotifyResult {
+        let mut result = Ok(());
+        for fissure in event.fissures() {
+            let values = template::values(event, fissure, OffsetDateTime::now_utc());
+            for webhook in &config.webhooks {
+                if let Err(e) = self.deliver(webhook, &values).await {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Makes the last attempt of the requests being retried right away, waiting for them to finish
+    async fn flush(&self, _config: &Config) -> NotifyResult {
+        self.stop.send_replace(true);
+        let mut retries = std::mem::take(&mut *self.retries.lock().unwrap());
+        while retries.join_next().await.is_some() {}
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use reqwest::StatusCode;
+
+    use super::*;
+    use crate::{models::Fissure, test_util::MockServer};
+
+    fn webhook(url: String, headers: &[(&str, &str)], body: &str) -> WebhookConfig {
+        WebhookConfig {
+            url: Template::parse(&url).unwrap(),
+            method: Method::POST,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), Template::parse(value).unwrap()))
+                .collect(),
+            body: Some(Template::parse(body).unwrap()),
+            retries: 0,
+        }
+    }
+
+    fn event() -> NotificationEvent {
+        let expiry = OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap();
+        NotificationEvent::NewFissures(vec![Fissure::sample(
+            "f1",
+            "Rock \"n\" Roll & Co (Sedna)",
+            expiry,
+        )])
+    }
+
+    async fn send(webhook: WebhookConfig) {
+        let config = Config {
+            webhooks: vec![webhook],
+            ..Config::default()
+        };
+        WebhookSink::new(mpsc::channel(1).0)
+            .notify(&event(), &config)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn escapes_values_in_the_url_and_json_body() {
+        let server = MockServer::start(|_| (StatusCode::OK, String::new()));
+        send(webhook(
+            server.url("/hook?node={node}&tier={tier}"),
+            &[("Content-Type", "application/json")],
+            "{{\"node\": \"{node}\", \"raw\": {is_hard|raw}}}",
+        ))
+        .await;
+
+        let requests = server.requests();
+        assert_eq!(
+            requests[0].uri,
+            "/hook?node=Rock%20%22n%22%20Roll%20%26%20Co%20%28Sedna%29&tier=Lith"
+        );
+        let body = requests[0].json();
+        assert_eq!(body["node"], "Rock \"n\" Roll & Co (Sedna)");
+        assert_eq!(body["raw"], false);
+    }
+
+    #[tokio::test]
+    async fn sends_plain_bodies_unescaped() {
+        let server = MockServer::start(|_| (StatusCode::OK, String::new()));
+        send(webhook(server.url("/hook"), &[], "{node|url} {node}")).await;
+
+        assert_eq!(
+            server.requests()[0].body,
+            "Rock%20%22n%22%20Roll%20%26%20Co%20%28Sedna%29 Rock \"n\" Roll & Co (Sedna)"
+        );
+    }
+
+    #[tokio::test]
+    async fn flush_makes_the_last_attempt_right_away() {
+        static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+        let server = MockServer::start(|_| match ATTEMPTS.fetch_add(1, Ordering::SeqCst) {
+            0 => (StatusCode::SERVICE_UNAVAILABLE, String::new()),
+            _ => (StatusCode::OK, String::new()),
+        });
+        let mut webhook = webhook(server.url("/hook"), &[], "{node}");
+        webhook.retries = 3;
+        let config = Config {
+            webhooks: vec![webhook],
+            ..Config::default()
+        };
+        let (app_tx, mut app_rx) = mpsc::channel(1);
+        let sink = WebhookSink::new(app_tx);
+        sink.notify(&event(), &config).await.unwrap();
+        assert!(matches!(
+            app_rx.recv().await,
+            Some(fissure_watcher::Event::Log(_))
+        ));
+
+        let start = std::time::Instant::now();
+        let (flushed, event) = tokio::join!(sink.flush(&config), app_rx.recv());
+        flushed.unwrap();
+        assert!(start.elapsed() < INITIAL_BACKOFF);
+        assert!(matches!(event, Some(fissure_watcher::Event::Log(_))));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn retries_in_the_background() {
+        static ATTEMPTS: AtomicUsize = AtomicUsize::new(0);
+        // fails the first attempt only
+        let server = MockServer::start(|_| match ATTEMPTS.fetch_add(1, Ordering::SeqCst) {
+            0 => (StatusCode::SERVICE_UNAVAILABLE, String::new()),
+            _ => (StatusCode::OK, String::new()),
+        });
+        let mut webhook = webhook(server.url("/hook"), &[], "{node}");
+        webhook.retries = 3;
+        let config = Config {
+            webhooks: vec![webhook],
+            ..Config::default()
+        };
+        let (app_tx, mut app_rx) = mpsc::channel(1);
+
+        let sink = WebhookSink::new(app_tx);
+        // the queued retry is not a failure
+        sink.notify(&event(), &config).await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+        let Some(fissure_watcher::Event::Log(message)) = app_rx.recv().await else {
+            panic!("expected the first failure to be logged");
+        };
+        assert!(message.ends_with("retrying in the background"));
+
+        let Some(fissure_watcher::Event::Log(message)) = app_rx.recv().await else {
+            panic!("expected the retry to be logged");
+        };
+        assert!(message.ends_with("succeeded after 2 attempts"));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn reports_requests_given_up_on() {
+        let server = MockServer::start(|_| (StatusCode::SERVICE_UNAVAILABLE, String::new()));
+        let mut webhook = webhook(server.url("/hook"), &[], "{node}");
+        webhook.retries = 1;
+        let config = Config {
+            webhooks: vec![webhook],
+            ..Config::default()
+        };
+        let (app_tx, mut app_rx) = mpsc::channel(1);
+        let mut sink = WebhookSink::new(app_tx);
+        let dead_letters = std::env::temp_dir().join(format!(
+            "reapers-wf-dead-letters-{}.jsonl",
+            std::process::id()
+        ));
+        sink.dead_letters = dead_letters.clone();
+        sink.notify(&event(), &config).await.unwrap();
+        assert!(matches!(
+            app_rx.recv().await,
+            Some(fissure_watcher::Event::Log(_))
+        ));
+
+        let Some(fissure_watcher::Event::Err(message)) = app_rx.recv().await else {
+            panic!("expected the failure to be reported");
+        };
+        assert!(message.starts_with("Webhook request failed after 2 attempts"));
+        assert_eq!(server.requests().len(), 2);
+        let letter = tokio::fs::read_to_string(&dead_letters).await.unwrap();
+        tokio::fs::remove_file(&dead_letters).await.unwrap();
+        let letter = serde_json::from_str::<serde_json::Value>(&letter).unwrap();
+        assert_eq!(letter["attempts"], 2);
+        assert_eq!(letter["body"], "Rock \"n\" Roll & Co (Sedna)");
+    }
+}
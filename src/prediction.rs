@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use time::{Duration, OffsetDateTime};
+
+use crate::{config::Config, filters::Tier, history::HistoryRecord, models::Fissure};
+
+/// How far ahead replacements of the current fissures are considered
+const HORIZON: Duration = Duration::days(7);
+/// Fraction of outcomes left out on each side of the confidence range
+const CONFIDENCE_TAIL: f64 = 0.1;
+
+/// Estimate of when the next fissure matching the filters will appear
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Prediction {
+    /// Expected time of the next matching fissure
+    pub expected: OffsetDateTime,
+    /// Lower end of the confidence range
+    pub earliest: OffsetDateTime,
+    /// Upper end of the confidence range, `None` if it lies beyond the prediction horizon
+    pub latest: Option<OffsetDateTime>,
+    /// Number of recorded fissures the estimate is based on
+    pub samples: usize,
+}
+
+/// Fissures only replace fissures of the same tier, on the same difficulty and of the same kind of node
+type Slot = (Tier, bool, bool);
+
+fn slot(tier: Tier, is_storm: bool, is_hard: bool) -> Slot {
+    (tier, is_storm, is_hard)
+}
+
+/// How likely a fissure spawning into a slot matches the filters, and how long fissures in it last
+struct SlotStatistics {
+    match_probability: f64,
+    average_duration: Duration,
+}
+
+/// Estimates when the next fissure matching the filters of the config will appear.
+///
+/// Every current fissure is replaced by a new one of the same tier when it expires, which in turn is replaced
+/// after the average duration of its tier and so on. Each replacement matches the filters with the probability
+/// observed in the history for its tier, which gives the distribution of the first matching replacement.
+/// # Returns
+/// `None` if the history contains no fissure that matches the filters.
+pub fn predict(
+    records: &[HistoryRecord],
+    fissures: &[Fissure],
+    config: &Config,
+    now: OffsetDateTime,
+) -> Option<Prediction> {
+    let mut counts = HashMap::<Slot, (usize, usize, Duration)>::new();
+    for record in records {
+        let (total, matching, duration) = counts
+            .entry(slot(record.tier, record.is_storm, record.is_hard))
+            .or_default();
+        *total += 1;
+        if record.matches(config) {
+            *matching += 1;
+        }
+        *duration += record.duration();
+    }
+    let slots = counts
+        .into_iter()
+        .filter(|(_, (_, matching, _))| *matching > 0)
+        .map(|(slot, (total, matching, duration))| {
+            (
+                slot,
+                SlotStatistics {
+                    match_probability: matching as f64 / total as f64,
+                    average_duration: duration / total as u32,
+                },
+            )
+        })
+        .collect::<HashMap<Slot, SlotStatistics>>();
+    if slots.is_empty() {
+        return None;
+    }
+    // times at which a replacement fissure appears, with the probability of it matching
+    let horizon = now + HORIZON;
+    let mut spawns = Vec::new();
+    for fissure in fissures {
+        let Some(stats) = slots.get(&slot(fissure.tier, fissure.is_storm, fissure.is_hard)) else {
+            continue;
+        };
+        let mut spawn = fissure.expiry.max(now);
+        while spawn < horizon {
+            spawns.push((spawn, stats.match_probability));
+            if stats.average_duration <= Duration::ZERO {
+                break;
+            }
+            spawn += stats.average_duration;
+        }
+    }
+    spawns.sort_by_key(|(spawn, _)| *spawn);
+    // walk through the spawns, tracking the probability that none of them matched so far
+    let mut no_match = 1.0;
+    let mut weighted_seconds = 0.0;
+    let mut earliest = None;
+    let mut latest = None;
+    for (spawn, probability) in spawns {
+        let first_match = no_match * probability;
+        weighted_seconds += (spawn - now).as_seconds_f64() * first_match;
+        no_match -= first_match;
+        let matched = 1.0 - no_match;
+        if earliest.is_none() && matched >= CONFIDENCE_TAIL {
+            earliest = Some(spawn);
+        }
+        if latest.is_none() && matched >= 1.0 - CONFIDENCE_TAIL {
+            latest = Some(spawn);
+        }
+    }
+    let matched = 1.0 - no_match;
+    if matched <= 0.0 {
+        return None;
+    }
+    // the expectation is conditional on a match within the horizon
+    let expected = now + Duration::seconds_f64(weighted_seconds / matched);
+    Some(Prediction {
+        expected,
+        earliest: earliest.unwrap_or(expected).min(expected),
+        latest,
+        samples: records.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mission_type::MissionType;
+
+    /// 2024-01-01 12:00 UTC
+    fn now() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap()
+    }
+
+    /// A Lith fissure of the given mission type that lasted an hour, the default config only matches Disruption
+    fn record(mission_type: MissionType) -> HistoryRecord {
+        let mut fissure = Fissure::sample("old", "Hydron (Sedna)", now() - Duration::days(1));
+        fissure.mission_type = mission_type;
+        HistoryRecord::from(&fissure)
+    }
+
+    fn current(minutes_left: i64) -> Fissure {
+        Fissure::sample(
+            "a",
+            "Hydron (Sedna)",
+            now() + Duration::minutes(minutes_left),
+        )
+    }
+
+    #[test]
+    fn certain_match_is_expected_when_the_current_fissure_expires() {
+        let records = [record(MissionType::Disruption)];
+        let prediction = predict(&records, &[current(30)], &Config::default(), now()).unwrap();
+        let expiry = now() + Duration::minutes(30);
+        assert_eq!(
+            prediction,
+            Prediction {
+                expected: expiry,
+                earliest: expiry,
+                latest: Some(expiry),
+                samples: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn confidence_range_follows_the_match_probability_of_the_slot() {
+        // half of the Lith fissures matched and they lasted an hour, so replacements spawn 30, 90, 150, ... minutes
+        // from now, each matching with a probability of 0.5
+        let records = [
+            record(MissionType::Disruption),
+            record(MissionType::Survival),
+        ];
+        let prediction = predict(&records, &[current(30)], &Config::default(), now()).unwrap();
+        assert_eq!(prediction.samples, 2);
+        // the first replacement already matches with a probability of 0.5, above the lower tail of 0.1
+        assert_eq!(prediction.earliest, now() + Duration::minutes(30));
+        // four replacements are needed to match with a probability of at least 0.9 (1 - 0.5^4)
+        assert_eq!(prediction.latest, Some(now() + Duration::minutes(210)));
+        // on average the second replacement is the first to match
+        let offset = prediction.expected - (now() + Duration::minutes(90));
+        assert!(offset.abs() < Duration::SECOND, "{offset}");
+    }
+
+    #[test]
+    fn expired_fissures_are_replaced_now() {
+        let records = [record(MissionType::Disruption)];
+        let prediction = predict(&records, &[current(-5)], &Config::default(), now()).unwrap();
+        assert_eq!(prediction.expected, now());
+    }
+
+    #[test]
+    fn none_without_enough_history() {
+        let config = Config::default();
+        // nothing recorded
+        assert_eq!(predict(&[], &[current(30)], &config, now()), None);
+        // nothing recorded ever matched the filters
+        let records = [record(MissionType::Survival)];
+        assert_eq!(predict(&records, &[current(30)], &config, now()), None);
+        // matches were only recorded for a different tier than the current fissures
+        let records = [record(MissionType::Disruption)];
+        let mut fissure = current(30);
+        fissure.tier = Tier::Axi;
+        assert_eq!(predict(&records, &[fissure], &config, now()), None);
+        assert_eq!(predict(&records, &[], &config, now()), None);
+    }
+}
@@ -18,7 +18,7 @@ pub fn proc_macro_derive_display(input: TokenStream) -> TokenStream {
                     Self::#name => write!(f, #name_str),
                 }
             });
-            let enum_name_str = &input.ident.to_string().split(" ").into_iter().last().unwrap().to_owned();
+            let enum_name_str = &input.ident.to_string().split(" ").last().unwrap().to_owned();
             let enum_name = proc_macro2::Ident::new(enum_name_str, Span::call_site()) ;
             let expanded = quote!{
                 impl std::fmt::Display for #enum_name {
@@ -47,7 +47,7 @@ pub fn proc_macro_derive_fromstr(input: TokenStream) -> TokenStream {
                     #name_str => Ok(Self::#name),
                 }
             });
-            let enum_name_str = &input.ident.to_string().split(" ").into_iter().last().unwrap().to_owned();
+            let enum_name_str = &input.ident.to_string().split(" ").last().unwrap().to_owned();
             let enum_name = proc_macro2::Ident::new(enum_name_str, Span::call_site()) ;
             let expanded = quote! {
                 impl std::str::FromStr for #enum_name {
@@ -66,4 +66,38 @@ pub fn proc_macro_derive_fromstr(input: TokenStream) -> TokenStream {
         }
         _ => panic!("FromStr can only be derived for enums"),
     }
-}
\ No newline at end of file
+}
+#[proc_macro_derive(Variants)]
+pub fn proc_macro_derive_variants(input: TokenStream) -> TokenStream {
+    // Parse the input tokens into a syntax tree
+    let input = parse_macro_input!(input as DeriveInput);
+    match input.data {
+        Data::Enum(ref innerdata) => {
+            let var = innerdata.variants.iter().map(|v| {
+                let name = &v.ident;
+                quote! {
+                    Self::#name,
+                }
+            });
+            let name_strs = innerdata.variants.iter().map(|v| {
+                let name_str = v.ident.to_string();
+                quote! {
+                    #name_str,
+                }
+            });
+            let enum_name_str = &input.ident.to_string().split(" ").last().unwrap().to_owned();
+            let enum_name = proc_macro2::Ident::new(enum_name_str, Span::call_site()) ;
+            let expanded = quote! {
+                impl #enum_name {
+                    /// All variants of this enum, in declaration order
+                    pub const VARIANTS: &'static [Self] = &[#(#var)*];
+                    /// The names of all variants, as accepted by `FromStr`
+                    pub const VARIANT_NAMES: &'static [&'static str] = &[#(#name_strs)*];
+                }
+            };
+
+            TokenStream::from(expanded)
+        }
+        _ => panic!("Variants can only be derived for enums"),
+    }
+}
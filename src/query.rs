@@ -0,0 +1,243 @@
+use std::error::Error;
+
+use crate::{
+    api,
+    cli::{OutputFormat, Selection, EXIT_CONFIG, EXIT_NO_MATCH, EXIT_UNAVAILABLE, EXIT_USAGE},
+    config::{Config, Profile, CONFIG_PATH},
+    models::Fissure,
+    util::table,
+};
+
+/// Columns of the CSV output, named like the fields of the JSON output
+const CSV_HEADERS: &[&str] = &[
+    "id",
+    "activation",
+    "expiry",
+    "startString",
+    "active",
+    "node",
+    "expired",
+    "eta",
+    "missionType",
+    "missionKey",
+    "tier",
+    "tierNum",
+    "enemy",
+    "enemyKey",
+    "isStorm",
+    "isHard",
+];
+
+/// The fissures printed with the profile of a `Selection` resolved
+enum Selected<'a> {
+    Matching,
+    Profile(&'a Profile),
+    All,
+}
+
+/// Prints the current fissures once, those matching the filters unless all are selected.
+/// # Returns
+/// The exit status of the process, 0 if any fissure was printed.
+pub async fn fissures(selection: Selection, format: OutputFormat) -> i32 {
+    let config = match load_config().await {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", CONFIG_PATH, e);
+            return EXIT_CONFIG;
+        }
+    };
+    let selected = match &selection {
+        Selection::Matching => Selected::Matching,
+        Selection::All => Selected::All,
+        Selection::Profile(name) => match config.find_profile(name) {
+            Some(profile) => Selected::Profile(profile),
+            None => {
+                let names = config
+                    .profiles()
+                    .map(|profile| profile.name.as_str())
+                    .collect::<Vec<&str>>();
+                eprintln!(
+                    "Unknown profile \"{}\", expected one of: {}",
+                    name,
+                    names.join(", ")
+                );
+                return EXIT_USAGE;
+            }
+        },
+    };
+    let fissures = match api::get_fissures(&config.api_url, &config.platform).await {
+        Ok(fetched) => {
+            if let Some(e) = fetched.fallback_reason {
+                eprintln!(
+                    "Failed to fetch fissures from {}, fetched them from {} instead: {}",
+                    config.api_url,
+                    api::UPSTREAM_URL,
+                    e
+                );
+            }
+            fetched.fissures
+        }
+        Err(e) => {
+            eprintln!("Failed to fetch fissures: {}", e);
+            return EXIT_UNAVAILABLE;
+        }
+    };
+    let fissures = select(fissures, &config, &selected);
+    match format {
+        OutputFormat::Table if fissures.is_empty() => {}
+        OutputFormat::Table => {
+            let mut rows = vec![Fissure::table_headers()];
+            rows.extend(fissures.iter().map(Fissure::table_string));
+            print!("{}", table(rows));
+        }
+        OutputFormat::Json => match serde_json::to_string_pretty(&fissures) {
+            Ok(json) => println!("{}", json),
+            Err(e) => {
+                eprintln!("Failed to serialize the fissures: {}", e);
+                return EXIT_UNAVAILABLE;
+            }
+        },
+        OutputFormat::Csv => print!("{}", csv(&fissures)),
+    }
+    exit_status(&fissures)
+}
+
+/// The selected fissures, sorted like in the terminal interface
+fn select(mut fissures: Vec<Fissure>, config: &Config, selected: &Selected) -> Vec<Fissure> {
+    match selected {
+        Selected::Matching => fissures.retain(|fissure| config.matches(fissure)),
+        Selected::Profile(profile) => fissures.retain(|fissure| {
+            !config.ignored_nodes.contains(&fissure.node) && profile.filter.matches(fissure)
+        }),
+        Selected::All => {}
+    }
+    fissures.sort_by_key(|fissure| (fissure.tier_num, fissure.expiry));
+    fissures
+}
+
+/// 0 if any fissure was printed, so that scripts can tell whether anything matched
+fn exit_status(printed: &[Fissure]) -> i32 {
+    if printed.is_empty() {
+        EXIT_NO_MATCH
+    } else {
+        0
+    }
+}
+
+/// Loads the config file, using the default config if there is none
+async fn load_config() -> Result<Config, Box<dyn Error>> {
+    match Config::load().await {
+        Err(e)
+            if e.downcast_ref::<std::io::Error>()
+                .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) =>
+        {
+            Ok(Config::default())
+        }
+        result => result,
+    }
+}
+
+/// The fissures as CSV with a header line, taking the values from their JSON representation
+fn csv(fissures: &[Fissure]) -> String {
+    let mut text = CSV_HEADERS.join(",");
+    text.push('\n');
+    for fissure in fissures {
+        let value = serde_json::to_value(fissure).unwrap_or_default();
+        let row = CSV_HEADERS
+            .iter()
+            .map(|header| match &value[header] {
+                serde_json::Value::String(text) => csv_field(text),
+                other => csv_field(&other.to_string()),
+            })
+            .collect::<Vec<String>>();
+        text.push_str(&row.join(","));
+        text.push('\n');
+    }
+    text
+}
+
+/// Quotes the field if it contains a separator, quote or line break
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mission_type::MissionType;
+
+    /// Fissures of the given mission types expiring in order, the default config only matches Disruption
+    fn fissures(missions: &[(&str, MissionType)]) -> Vec<Fissure> {
+        let expiry = time::OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap();
+        missions
+            .iter()
+            .enumerate()
+            .map(|(i, (id, mission_type))| {
+                let mut fissure = Fissure::sample(
+                    id,
+                    "Hydron (Sedna)",
+                    expiry - time::Duration::minutes(i as i64),
+                );
+                fissure.mission_type = *mission_type;
+                fissure
+            })
+            .collect()
+    }
+
+    fn ids(fissures: &[Fissure]) -> Vec<&str> {
+        fissures.iter().map(|fissure| fissure.id.as_str()).collect()
+    }
+
+    #[test]
+    fn selects_matching_fissures_by_default() {
+        let mut config = Config::default();
+        let mut fissures = fissures(&[
+            ("survival", MissionType::Survival),
+            ("disruption", MissionType::Disruption),
+            ("ignored", MissionType::Disruption),
+        ]);
+        fissures[2].node = "Apollo (Lua)".to_string();
+        config.ignored_nodes = vec!["Apollo (Lua)".to_string()];
+
+        let selected = select(fissures.clone(), &config, &Selected::Matching);
+        assert_eq!(ids(&selected), ["disruption"]);
+        assert_eq!(exit_status(&selected), 0);
+
+        // sorted by tier and expiry
+        let all = select(fissures.clone(), &config, &Selected::All);
+        assert_eq!(ids(&all), ["ignored", "disruption", "survival"]);
+
+        let mut profile = config.default_profile.clone();
+        profile.filter.mission_filter = vec![MissionType::Survival];
+        let selected = select(fissures, &config, &Selected::Profile(&profile));
+        assert_eq!(ids(&selected), ["survival"]);
+    }
+
+    #[test]
+    fn exit_status_tells_whether_anything_matched() {
+        let fissures = fissures(&[("survival", MissionType::Survival)]);
+        let selected = select(fissures.clone(), &Config::default(), &Selected::Matching);
+        assert_eq!(exit_status(&selected), EXIT_NO_MATCH);
+        assert_eq!(exit_status(&fissures), 0);
+    }
+
+    #[test]
+    fn csv_has_a_header_and_quotes_fields() {
+        let mut fissures = fissures(&[("a", MissionType::Survival)]);
+        fissures[0].node = "Rock \"n\" Roll, Sedna".to_string();
+        let text = csv(&fissures);
+        let lines = text.lines().collect::<Vec<&str>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], CSV_HEADERS.join(","));
+        assert_eq!(
+            lines[1],
+            "a,2024-01-01T11:00:00Z,2024-01-01T12:00:00Z,-10m,true,\"Rock \"\"n\"\" Roll, Sedna\",false,50m,\
+             Survival,Survival,Lith,1,Grineer,Grineer,false,false"
+        );
+        assert_eq!(csv(&[]), format!("{}\n", CSV_HEADERS.join(",")));
+    }
+}
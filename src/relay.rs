@@ -0,0 +1,270 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    sync::Mutex,
+    time::Duration,
+};
+
+use hyper::{
+    header::{HeaderValue, AGE, CACHE_CONTROL, ETAG, IF_NONE_MATCH, LAST_MODIFIED},
+    Body, Request, Response, StatusCode,
+};
+use serde_json::{json, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    api,
+    server::{error, response},
+};
+
+/// Header telling clients whether the relayed fissures are current: "ok", "stale" while the public API fails and
+/// the last fetched fissures are served, or "down" on the error response when none could be fetched yet
+const UPSTREAM_HEALTH: &str = "x-upstream-health";
+
+/// The last fetch of the fissures of a platform
+#[derive(Clone)]
+struct Cached {
+    /// The body of the last successful fetch, as returned by the API
+    body: Option<String>,
+    etag: String,
+    /// When the body was fetched
+    fetched: Option<OffsetDateTime>,
+    /// When the API was last asked, successfully or not
+    checked: OffsetDateTime,
+    /// Why the last fetch failed, `None` if it succeeded
+    error: Option<String>,
+}
+
+/// Re-serves the fissures of the public API to other instances, fetching them at most once per `max_age`
+pub struct Relay {
+    max_age: Duration,
+    /// Only locked briefly and never while fetching, so that fetching does not hold up other requests
+    cache: Mutex<HashMap<String, Cached>>,
+    /// Held while fetching the fissures of a platform, so that concurrent requests wait for one fetch instead of
+    /// each fetching
+    fetching: HashMap<&'static str, tokio::sync::Mutex<()>>,
+}
+
+impl Relay {
+    pub fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            cache: Mutex::new(HashMap::new()),
+            fetching: api::PLATFORMS
+                .iter()
+                .map(|platform| (*platform, tokio::sync::Mutex::new(())))
+                .collect(),
+        }
+    }
+
+    /// Responds like `/{platform}/fissures` of the public API, serving the cached fissures while they are fresh and
+    /// the last fetched ones while the API fails
+    pub async fn fissures(&self, platform: &str, request: &Request<Body>) -> Response<Body> {
+        let Some(fetching) = self.fetching.get(platform) else {
+            return error(
+                StatusCode::NOT_FOUND,
+                &format!(
+                    "Unknown platform \"{}\", expected one of: {}",
+                    platform,
+                    api::PLATFORMS.join(", ")
+                ),
+            );
+        };
+        if self.cached(platform).is_none() {
+            let _fetching = fetching.lock().await;
+            // another request may have fetched them while this one waited
+            if self.cached(platform).is_none() {
+                let result = fetch(platform).await;
+                self.update(platform, result);
+            }
+        }
+        let Some(cached) = self.cache.lock().unwrap().get(platform).cloned() else {
+            return error(StatusCode::BAD_GATEWAY, "Failed to fetch the fissures");
+        };
+        let (Some(body), Some(fetched)) = (cached.body, cached.fetched) else {
+            let message = format!(
+                "Failed to fetch the fissures: {}",
+                cached.error.as_deref().unwrap_or_default()
+            );
+            let mut response = error(StatusCode::BAD_GATEWAY, &message);
+            response
+                .headers_mut()
+                .insert(UPSTREAM_HEALTH, HeaderValue::from_static("down"));
+            return response;
+        };
+        let mut response = if matches_etag(request.headers().get(IF_NONE_MATCH), &cached.etag) {
+            response(StatusCode::NOT_MODIFIED, "application/json", Body::empty())
+        } else {
+            response(StatusCode::OK, "application/json", body.into())
+        };
+        let now = OffsetDateTime::now_utc();
+        let max_age = self
+            .max_age
+            .saturating_sub((now - cached.checked).try_into().unwrap_or_default());
+        let headers = response.headers_mut();
+        let mut insert = |name, value: String| {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(name, value);
+            }
+        };
+        insert(
+            CACHE_CONTROL,
+            format!("public, max-age={}", max_age.as_secs()),
+        );
+        insert(AGE, (now - fetched).whole_seconds().max(0).to_string());
+        insert(LAST_MODIFIED, http_date(fetched));
+        insert(ETAG, cached.etag);
+        let health = if cached.error.is_none() {
+            "ok"
+        } else {
+            "stale"
+        };
+        headers.insert(UPSTREAM_HEALTH, HeaderValue::from_static(health));
+        response
+    }
+
+    /// Stores fissures fetched elsewhere, e.g. by the watcher, as if the relay had fetched them
+    pub fn store(&self, platform: &str, body: String) {
+        if self.fetching.contains_key(platform) {
+            self.update(platform, Ok(body));
+        }
+    }
+
+    /// The cached fetch of the platform, `None` if it is missing or older than `max_age`
+    fn cached(&self, platform: &str) -> Option<Cached> {
+        let now = OffsetDateTime::now_utc();
+        self.cache
+            .lock()
+            .unwrap()
+            .get(platform)
+            .filter(|cached| now - cached.checked < self.max_age)
+            .cloned()
+    }
+
+    fn update(&self, platform: &str, result: Result<String, String>) {
+        let now = OffsetDateTime::now_utc();
+        let mut cache = self.cache.lock().unwrap();
+        let cached = cache.entry(platform.to_string()).or_insert(Cached {
+            body: None,
+            etag: String::new(),
+            fetched: None,
+            checked: now,
+            error: None,
+        });
+        cached.checked = now;
+        match result {
+            Ok(body) => {
+                cached.etag = etag(&body);
+                cached.body = Some(body);
+                cached.fetched = Some(now);
+                cached.error = None;
+            }
+            Err(e) => cached.error = Some(e),
+        }
+    }
+
+    /// Whether the last fetch of each requested platform succeeded, and when the fissures were last fetched
+    pub fn health(&self) -> Value {
+        let cache = self.cache.lock().unwrap();
+        let platforms = cache
+            .iter()
+            .map(|(platform, cached)| {
+                let format = |time: OffsetDateTime| time.format(&Rfc3339).unwrap_or_default();
+                let health = json!({
+                    "healthy": cached.error.is_none(),
+                    "error": cached.error,
+                    "last_success": cached.fetched.map(format),
+                    "last_checked": format(cached.checked),
+                });
+                (platform.clone(), health)
+            })
+            .collect::<serde_json::Map<String, Value>>();
+        json!({
+            "upstream": api::UPSTREAM_URL,
+            "upstream_healthy": cache.values().all(|cached| cached.error.is_none()),
+            "platforms": platforms,
+        })
+    }
+}
+
+/// Whether the If-None-Match header lists the entity tag, which is compared weakly as HTTP requires
+fn matches_etag(if_none_match: Option<&HeaderValue>, etag: &str) -> bool {
+    let Some(tags) = if_none_match.and_then(|tags| tags.to_str().ok()) else {
+        return false;
+    };
+    tags.split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Fetches the fissures from the public API, making sure they are a JSON list before relaying them
+async fn fetch(platform: &str) -> Result<String, String> {
+    let body = api::get_fissures_text(api::UPSTREAM_URL, platform)
+        .await
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str::<Vec<Value>>(&body)
+        .map_err(|e| format!("the API returned an invalid fissure list: {}", e))?;
+    Ok(body)
+}
+
+fn etag(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// The time in the format of HTTP headers, e.g. "Sun, 06 Nov 1994 08:49:37 GMT"
+fn http_date(time: OffsetDateTime) -> String {
+    let format = time::format_description::parse(
+        "[weekday repr:short], [day] [month repr:short] [year] [hour]:[minute]:[second] GMT",
+    )
+    .unwrap();
+    time.to_offset(time::UtcOffset::UTC)
+        .format(&format)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(if_none_match: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder().uri("/pc/fissures");
+        if let Some(tags) = if_none_match {
+            request = request.header(IF_NONE_MATCH, tags);
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn parses_if_none_match_lists() {
+        let etag = "\"0123456789abcdef\"";
+        let matches = |tags: &str| matches_etag(Some(&HeaderValue::from_str(tags).unwrap()), etag);
+        assert!(matches(etag));
+        assert!(matches("\"other\", W/\"0123456789abcdef\""));
+        assert!(matches("*"));
+        assert!(!matches("\"other\", W/\"another\""));
+        assert!(!matches_etag(None, etag));
+    }
+
+    #[tokio::test]
+    async fn serves_stored_fissures() {
+        let relay = Relay::new(Duration::from_secs(60));
+        relay.store("pc", "[]".to_string());
+
+        let response = relay.fissures("pc", &request(None)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[UPSTREAM_HEALTH], "ok");
+        let etag = response.headers()[ETAG].to_str().unwrap().to_string();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(body, "[]");
+
+        let tags = format!("\"other\", W/{}", etag);
+        let response = relay.fissures("pc", &request(Some(&tags))).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = relay.fissures("pc4", &request(None)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(relay.health()["platforms"]["pc"]["healthy"], true);
+    }
+}
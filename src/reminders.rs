@@ -0,0 +1,322 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    time::Duration,
+};
+
+use time::OffsetDateTime;
+
+use crate::models::Fissure;
+
+/// Source of the current time, so that the scheduler can be driven by something other than the system clock
+pub trait Clock {
+    fn now(&self) -> OffsetDateTime;
+}
+
+pub struct SystemClock;
+impl Clock for SystemClock {
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A pending notification about a fissure that is about to expire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reminder {
+    pub fissure: Fissure,
+    /// When the reminder should be sent
+    pub due: OffsetDateTime,
+}
+
+/// Keeps at most one pending expiry reminder per fissure id, ordered by when they are due
+pub struct ReminderScheduler<C: Clock = SystemClock> {
+    clock: C,
+    /// How long before a fissure's expiry its reminder is due
+    lead_time: Duration,
+    /// Due times and ids of the reminders, may contain stale entries of cancelled or rescheduled reminders
+    queue: BinaryHeap<Reverse<(OffsetDateTime, String)>>,
+    pending: HashMap<String, Reminder>,
+}
+
+impl<C: Clock> ReminderScheduler<C> {
+    pub fn new(clock: C, lead_time: Duration) -> Self {
+        Self {
+            clock,
+            lead_time,
+            queue: BinaryHeap::new(),
+            pending: HashMap::new(),
+        }
+    }
+
+    pub fn now(&self) -> OffsetDateTime {
+        self.clock.now()
+    }
+
+    /// Schedules a reminder for the given fissure, replacing any reminder already pending for it.
+    /// # Returns
+    /// Whether a reminder was scheduled, which is not the case if it would already be due.
+    pub fn schedule(&mut self, fissure: &Fissure) -> bool {
+        let due = fissure.expiry - self.lead_time;
+        if due <= self.clock.now() {
+            self.cancel(&fissure.id);
+            return false;
+        }
+        self.reschedule(fissure.clone(), due);
+        true
+    }
+
+    /// Schedules a reminder for the given fissure at an arbitrary time, replacing any reminder already pending for it
+    pub fn reschedule(&mut self, fissure: Fissure, due: OffsetDateTime) {
+        self.queue.push(Reverse((due, fissure.id.clone())));
+        self.pending
+            .insert(fissure.id.clone(), Reminder { fissure, due });
+    }
+
+    /// Cancels the reminder pending for the given fissure id, returning whether there was one
+    pub fn cancel(&mut self, id: &str) -> bool {
+        // the queue entry is dropped lazily once it reaches the front
+        self.pending.remove(id).is_some()
+    }
+
+    /// Cancels all pending reminders whose fissure does not satisfy `keep`
+    pub fn retain(&mut self, mut keep: impl FnMut(&Fissure) -> bool) {
+        self.pending.retain(|_, reminder| keep(&reminder.fissure));
+    }
+
+    /// Changes how long before expiry reminders are due, rescheduling all pending reminders.
+    /// Reminders of fissures snoozed until a later time, as returned by `snoozed_until`, stay at that time.
+    pub fn set_lead_time(
+        &mut self,
+        lead_time: Duration,
+        snoozed_until: impl Fn(&str) -> Option<OffsetDateTime>,
+    ) {
+        if lead_time == self.lead_time {
+            return;
+        }
+        self.lead_time = lead_time;
+        let fissures = self
+            .pending
+            .drain()
+            .map(|(_, reminder)| reminder.fissure)
+            .collect::<Vec<Fissure>>();
+        self.queue.clear();
+        let now = self.clock.now();
+        for fissure in fissures {
+            match snoozed_until(&fissure.id) {
+                Some(until) if until > now => {
+                    let due = until.max(fissure.expiry - self.lead_time);
+                    self.reschedule(fissure, due);
+                }
+                _ => {
+                    self.schedule(&fissure);
+                }
+            }
+        }
+    }
+
+    /// Drops queue entries that no longer match a pending reminder
+    fn discard_stale(&mut self) {
+        while let Some(Reverse((due, id))) = self.queue.peek() {
+            match self.pending.get(id) {
+                Some(reminder) if reminder.due == *due => return,
+                _ => {
+                    self.queue.pop();
+                }
+            }
+        }
+    }
+
+    /// When the earliest pending reminder is due
+    pub fn next_due(&mut self) -> Option<OffsetDateTime> {
+        self.discard_stale();
+        self.queue.peek().map(|Reverse((due, _))| *due)
+    }
+
+    /// Removes and returns all reminders that are due, earliest first
+    pub fn pop_due(&mut self) -> Vec<Reminder> {
+        let now = self.clock.now();
+        let mut due = Vec::new();
+        while self.next_due().is_some_and(|next| next <= now) {
+            let Reverse((_, id)) = self.queue.pop().unwrap();
+            due.extend(self.pending.remove(&id));
+        }
+        due
+    }
+
+    /// All pending reminders, earliest first
+    pub fn pending(&self) -> Vec<Reminder> {
+        let mut pending = self.pending.values().cloned().collect::<Vec<Reminder>>();
+        pending.sort_by_key(|reminder| reminder.due);
+        pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, rc::Rc};
+
+    use super::*;
+
+    /// A clock that only moves when told to, shared between the test and the scheduler
+    #[derive(Clone)]
+    struct ManualClock(Rc<Cell<OffsetDateTime>>);
+
+    impl ManualClock {
+        fn new(now: OffsetDateTime) -> Self {
+            Self(Rc::new(Cell::new(now)))
+        }
+
+        fn advance(&self, duration: Duration) {
+            self.0.set(self.0.get() + duration);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> OffsetDateTime {
+            self.0.get()
+        }
+    }
+
+    /// 2024-01-01 12:00 UTC
+    fn start() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap()
+    }
+    const LEAD_TIME: Duration = Duration::from_secs(5 * 60);
+
+    fn scheduler() -> (ManualClock, ReminderScheduler<ManualClock>) {
+        let clock = ManualClock::new(start());
+        (clock.clone(), ReminderScheduler::new(clock, LEAD_TIME))
+    }
+
+    fn fissure(id: &str, minutes_left: i64) -> Fissure {
+        Fissure::sample(
+            id,
+            "Hydron (Sedna)",
+            start() + time::Duration::minutes(minutes_left),
+        )
+    }
+
+    fn ids(reminders: &[Reminder]) -> Vec<&str> {
+        reminders
+            .iter()
+            .map(|reminder| reminder.fissure.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn schedule_is_due_lead_time_before_expiry() {
+        let (_, mut reminders) = scheduler();
+        assert!(reminders.schedule(&fissure("a", 30)));
+        assert_eq!(
+            reminders.next_due(),
+            Some(start() + time::Duration::minutes(25))
+        );
+        assert_eq!(ids(&reminders.pending()), ["a"]);
+    }
+
+    #[test]
+    fn schedule_skips_fissures_expiring_within_the_lead_time() {
+        let (_, mut reminders) = scheduler();
+        assert!(!reminders.schedule(&fissure("a", 4)));
+        assert_eq!(reminders.next_due(), None);
+    }
+
+    #[test]
+    fn scheduling_again_replaces_the_pending_reminder() {
+        let (_, mut reminders) = scheduler();
+        reminders.schedule(&fissure("a", 30));
+        reminders.schedule(&fissure("a", 60));
+        assert_eq!(reminders.pending().len(), 1);
+        assert_eq!(
+            reminders.next_due(),
+            Some(start() + time::Duration::minutes(55))
+        );
+    }
+
+    #[test]
+    fn cancel_removes_the_reminder() {
+        let (clock, mut reminders) = scheduler();
+        reminders.schedule(&fissure("a", 30));
+        reminders.schedule(&fissure("b", 40));
+        assert!(reminders.cancel("a"));
+        assert!(!reminders.cancel("a"));
+        assert_eq!(
+            reminders.next_due(),
+            Some(start() + time::Duration::minutes(35))
+        );
+        clock.advance(Duration::from_secs(60 * 60));
+        assert_eq!(ids(&reminders.pop_due()), ["b"]);
+    }
+
+    #[test]
+    fn reschedule_moves_the_reminder() {
+        let (clock, mut reminders) = scheduler();
+        reminders.schedule(&fissure("a", 30));
+        reminders.reschedule(fissure("a", 30), start() + time::Duration::minutes(10));
+        clock.advance(Duration::from_secs(10 * 60));
+        assert_eq!(ids(&reminders.pop_due()), ["a"]);
+        // the entry at the original due time is stale and must not fire again
+        clock.advance(Duration::from_secs(60 * 60));
+        assert!(reminders.pop_due().is_empty());
+    }
+
+    #[test]
+    fn pop_due_returns_only_due_reminders_earliest_first() {
+        let (clock, mut reminders) = scheduler();
+        reminders.schedule(&fissure("late", 50));
+        reminders.schedule(&fissure("early", 20));
+        reminders.schedule(&fissure("middle", 30));
+        assert!(reminders.pop_due().is_empty());
+        clock.advance(Duration::from_secs(25 * 60));
+        assert_eq!(ids(&reminders.pop_due()), ["early", "middle"]);
+        assert_eq!(ids(&reminders.pending()), ["late"]);
+        clock.advance(Duration::from_secs(25 * 60));
+        assert_eq!(ids(&reminders.pop_due()), ["late"]);
+        assert_eq!(reminders.next_due(), None);
+    }
+
+    #[test]
+    fn set_lead_time_reschedules_pending_reminders() {
+        let (_, mut reminders) = scheduler();
+        reminders.schedule(&fissure("a", 30));
+        reminders.schedule(&fissure("b", 8));
+        reminders.set_lead_time(Duration::from_secs(10 * 60), |_| None);
+        // "b" would already be due and is dropped
+        assert_eq!(ids(&reminders.pending()), ["a"]);
+        assert_eq!(
+            reminders.next_due(),
+            Some(start() + time::Duration::minutes(20))
+        );
+    }
+
+    #[test]
+    fn set_lead_time_keeps_pending_snoozes() {
+        let (_, mut reminders) = scheduler();
+        let snoozed_until = |id: &str| match id {
+            "a" | "b" => Some(start() + time::Duration::minutes(15)),
+            _ => None,
+        };
+        for (id, minutes_left) in [("a", 30), ("b", 60), ("c", 30)] {
+            reminders.reschedule(
+                fissure(id, minutes_left),
+                snoozed_until(id).unwrap_or(start() + time::Duration::minutes(minutes_left - 5)),
+            );
+        }
+        reminders.set_lead_time(Duration::from_secs(20 * 60), snoozed_until);
+        let due = reminders
+            .pending()
+            .into_iter()
+            .map(|reminder| (reminder.fissure.id, reminder.due))
+            .collect::<Vec<(String, OffsetDateTime)>>();
+        assert_eq!(
+            due,
+            [
+                ("c".to_string(), start() + time::Duration::minutes(10)),
+                // the snooze ends after the new lead time and is kept
+                ("a".to_string(), start() + time::Duration::minutes(15)),
+                // the new lead time is after the snooze
+                ("b".to_string(), start() + time::Duration::minutes(40)),
+            ]
+        );
+    }
+}
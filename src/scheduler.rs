@@ -0,0 +1,155 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use time::OffsetDateTime;
+
+use crate::{config::Config, models::Fissure};
+
+/// Decides when the fissures should next be fetched, based on when the known fissures expire
+pub struct PollScheduler {
+    /// Time between refreshes when adaptive refreshing is disabled
+    pub interval: Duration,
+    pub adaptive: bool,
+    /// Time between refreshes while a replacement for an expired fissure is expected
+    pub min_interval: Duration,
+    /// Longest time between refreshes while no fissure is about to expire
+    pub max_interval: Duration,
+    /// How long after a fissure expires its replacement is expected to appear
+    pub expiry_window: Duration,
+    /// Expiry times of all fissures seen that are upcoming or still inside their window
+    expiries: BTreeSet<OffsetDateTime>,
+}
+
+impl PollScheduler {
+    pub fn new(config: &Config, interval: Duration) -> Self {
+        let mut scheduler = Self {
+            interval,
+            adaptive: false,
+            min_interval: Duration::ZERO,
+            max_interval: Duration::ZERO,
+            expiry_window: Duration::ZERO,
+            expiries: BTreeSet::new(),
+        };
+        scheduler.configure(config);
+        scheduler
+    }
+
+    /// Updates the adaptive refresh settings from the config
+    pub fn configure(&mut self, config: &Config) {
+        self.adaptive = config.adaptive_refresh;
+        let min_refresh_rate = config.min_refresh_rate.max(1);
+        self.min_interval = Duration::from_secs(min_refresh_rate);
+        self.max_interval = Duration::from_secs(config.max_refresh_rate.max(min_refresh_rate));
+        self.expiry_window = Duration::from_secs(config.expiry_window);
+    }
+
+    /// Remembers the expiry times of the given fissures, and forgets those whose window has passed
+    pub fn observe(&mut self, now: OffsetDateTime, fissures: &[Fissure]) {
+        self.expiries
+            .extend(fissures.iter().map(|fissure| fissure.expiry));
+        let window = self.expiry_window;
+        self.expiries.retain(|expiry| *expiry + window > now);
+    }
+
+    /// Returns how long to wait before the next refresh.
+    ///
+    /// New fissures appear shortly after old ones expire, so while `now` is inside the window after
+    /// a known expiry the fissures are fetched every `min_interval`. Outside of it the next refresh
+    /// is timed to land just after the next expiry, but never later than `max_interval`.
+    pub fn next_delay(&self, now: OffsetDateTime) -> Duration {
+        if !self.adaptive {
+            return self.interval;
+        }
+        let in_window = self
+            .expiries
+            .range(..=now)
+            .any(|expiry| now < *expiry + self.expiry_window);
+        if in_window {
+            return self.min_interval;
+        }
+        self.expiries
+            .range(now..)
+            .next()
+            .map(|expiry| Duration::try_from(*expiry - now).unwrap_or_default() + self.min_interval)
+            .unwrap_or(self.max_interval)
+            .clamp(self.min_interval, self.max_interval)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01 12:00 UTC
+    fn now() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap()
+    }
+
+    fn fissure(seconds_left: i64) -> Fissure {
+        Fissure::sample(
+            "a",
+            "Hydron (Sedna)",
+            now() + time::Duration::seconds(seconds_left),
+        )
+    }
+
+    /// Refreshes between 30 and 300 seconds apart, every 30 seconds for 180 seconds after an expiry
+    fn scheduler(fissures: &[Fissure]) -> PollScheduler {
+        let mut scheduler = PollScheduler::new(&Config::default(), Duration::from_secs(300));
+        scheduler.observe(now(), fissures);
+        scheduler
+    }
+
+    #[test]
+    fn without_fissures_waits_the_longest_interval() {
+        assert_eq!(scheduler(&[]).next_delay(now()), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn lands_just_after_the_next_expiry() {
+        let scheduler = scheduler(&[fissure(200), fissure(100)]);
+        assert_eq!(scheduler.next_delay(now()), Duration::from_secs(130));
+    }
+
+    #[test]
+    fn delay_is_clamped_to_the_refresh_rates() {
+        // the next expiry is too far away
+        let scheduler = scheduler(&[fissure(3600)]);
+        assert_eq!(scheduler.next_delay(now()), Duration::from_secs(300));
+
+        // a maximum below the minimum is raised to it, and the minimum is at least a second
+        let mut config = Config {
+            min_refresh_rate: 60,
+            max_refresh_rate: 10,
+            ..Config::default()
+        };
+        let mut scheduler = PollScheduler::new(&config, Duration::from_secs(300));
+        assert_eq!(scheduler.next_delay(now()), Duration::from_secs(60));
+        config.min_refresh_rate = 0;
+        config.max_refresh_rate = 0;
+        scheduler.configure(&config);
+        assert_eq!(scheduler.next_delay(now()), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn polls_quickly_within_the_window_after_an_expiry() {
+        let mut scheduler = scheduler(&[fissure(0), fissure(1000)]);
+        let later = |seconds| now() + time::Duration::seconds(seconds);
+        assert_eq!(scheduler.next_delay(later(0)), Duration::from_secs(30));
+        assert_eq!(scheduler.next_delay(later(179)), Duration::from_secs(30));
+
+        // the window is over and the expiry forgotten
+        scheduler.observe(later(180), &[]);
+        assert_eq!(scheduler.next_delay(later(180)), Duration::from_secs(300));
+    }
+
+    #[test]
+    fn fixed_interval_without_adaptive_refresh() {
+        let config = Config {
+            adaptive_refresh: false,
+            ..Config::default()
+        };
+        let mut scheduler = PollScheduler::new(&config, Duration::from_secs(120));
+        scheduler.observe(now(), &[fissure(0)]);
+        assert_eq!(scheduler.next_delay(now()), Duration::from_secs(120));
+    }
+}
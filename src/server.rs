@@ -0,0 +1,412 @@
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+
+use hyper::{
+    header::{ACCESS_CONTROL_ALLOW_ORIGIN, CACHE_CONTROL, CONTENT_TYPE, ORIGIN, VARY},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    sync::{broadcast, mpsc, watch, RwLock},
+    task::JoinHandle,
+};
+
+use crate::{
+    config::Config,
+    fissure_watcher::{self, FissureEvent, RemovalReason},
+    models::Fissure,
+    relay::Relay,
+};
+
+/// How often a comment is sent on idle event streams, so that proxies keep them open
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// The embedded HTTP server, read when the app starts
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ServerConfig {
+    /// Address and port to listen on, only reachable from this machine by default
+    #[serde(default = "default_bind")]
+    pub bind: SocketAddr,
+    /// Whether to re-serve the fissures of the public API at `/{platform}/fissures` for other instances
+    #[serde(default)]
+    pub relay: bool,
+    /// How long the relay serves fetched fissures before fetching them again, in seconds
+    #[serde(default = "default_relay_cache_seconds")]
+    pub relay_cache_seconds: u64,
+    /// Origins of web pages that may read the responses, e.g. browser overlays. `"*"` allows any page
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+}
+
+fn default_bind() -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], 7878))
+}
+fn default_relay_cache_seconds() -> u64 {
+    60
+}
+
+/// A change the watcher streams to the clients of `/events`
+#[derive(Debug, Clone, Serialize)]
+pub struct StreamEvent {
+    /// "added", "changed", "removed" or "expiring"
+    pub event: &'static str,
+    /// "expired" or "vanished" for removed fissures
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<&'static str>,
+    /// Whether the fissure matches the filters of any profile
+    pub matches: bool,
+    pub fissure: Fissure,
+}
+
+impl StreamEvent {
+    pub fn new(event: &FissureEvent, matches: bool) -> Self {
+        let (kind, reason) = match event {
+            FissureEvent::Added(_) => ("added", None),
+            FissureEvent::Removed { reason, .. } => (
+                "removed",
+                Some(match reason {
+                    RemovalReason::Expired => "expired",
+                    RemovalReason::Vanished => "vanished",
+                }),
+            ),
+            FissureEvent::Changed { .. } => ("changed", None),
+        };
+        Self {
+            event: kind,
+            reason,
+            matches,
+            fissure: event.fissure().clone(),
+        }
+    }
+
+    /// The expiry reminder of a fissure matching the filters is due
+    pub fn expiring(fissure: Fissure) -> Self {
+        Self {
+            event: "expiring",
+            reason: None,
+            matches: true,
+            fissure,
+        }
+    }
+}
+
+/// What the watcher shares with the server, cheap to clone
+#[derive(Clone)]
+pub struct Feed {
+    /// All current fissures, `None` until they were fetched
+    fissures: Arc<watch::Sender<Option<Vec<Fissure>>>>,
+    events: broadcast::Sender<StreamEvent>,
+    /// Set once the server started, if relaying is enabled
+    relay: Arc<OnceLock<Relay>>,
+}
+
+impl Feed {
+    fn new() -> Self {
+        Self {
+            fissures: Arc::new(watch::channel(None).0),
+            events: broadcast::channel(64).0,
+            relay: Arc::new(OnceLock::new()),
+        }
+    }
+
+    /// Hands the fissures the watcher fetched to the relay, if relaying is enabled
+    pub fn relay_fetched(&self, platform: &str, body: String) {
+        if let Some(relay) = self.relay.get() {
+            relay.store(platform, body);
+        }
+    }
+
+    pub fn set_fissures(&self, fissures: Vec<Fissure>) {
+        self.fissures.send_replace(Some(fissures));
+    }
+
+    /// Streams the event to the connected clients, if any
+    pub fn send(&self, event: StreamEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn fissures(&self) -> Option<Vec<Fissure>> {
+        self.fissures.borrow().clone()
+    }
+}
+
+/// What the request handlers share
+struct Shared {
+    config: Arc<RwLock<Config>>,
+    feed: Feed,
+    allowed_origins: Vec<String>,
+}
+
+/// Spawns the HTTP server if the config has a `[server]` section, reporting failures to bind to the app.
+/// # Returns
+/// The feed the watcher updates, and the handle of the task.
+pub fn spawn(
+    config: Arc<RwLock<Config>>,
+    app_tx: mpsc::Sender<fissure_watcher::Event>,
+) -> (Feed, JoinHandle<()>) {
+    let feed = Feed::new();
+    let handle = tokio::spawn({
+        let feed = feed.clone();
+        async move {
+            let Some(server) = config.read().await.server.clone() else {
+                return;
+            };
+            if server.relay {
+                let relay = Relay::new(Duration::from_secs(server.relay_cache_seconds));
+                let _ = feed.relay.set(relay);
+            }
+            let shared = Arc::new(Shared {
+                config,
+                feed,
+                allowed_origins: server.allowed_origins.clone(),
+            });
+            let make_service = make_service_fn(move |_| {
+                let shared = Arc::clone(&shared);
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request| {
+                        handle(request, Arc::clone(&shared))
+                    }))
+                }
+            });
+            let result = match Server::try_bind(&server.bind) {
+                Ok(builder) => {
+                    let message = format!("HTTP server listening on http://{}", server.bind);
+                    let _ = app_tx.send(fissure_watcher::Event::Log(message)).await;
+                    builder.serve(make_service).await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                let message = format!("HTTP server on {} failed: {}", server.bind, e);
+                let _ = app_tx.send(fissure_watcher::Event::Err(message)).await;
+            }
+        }
+    });
+    (feed, handle)
+}
+
+async fn handle(request: Request<Body>, shared: Arc<Shared>) -> Result<Response<Body>, Infallible> {
+    let Shared {
+        config,
+        feed,
+        allowed_origins,
+    } = &*shared;
+    let mut response = if request.method() != Method::GET {
+        error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "Only GET requests are supported",
+        )
+    } else {
+        route(&request, config, feed).await
+    };
+    let origin = request
+        .headers()
+        .get(ORIGIN)
+        .and_then(|origin| origin.to_str().ok());
+    if let Some(allowed) = allowed_origin(origin, allowed_origins) {
+        let headers = response.headers_mut();
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allowed.parse().unwrap());
+        if allowed != "*" {
+            headers.insert(VARY, "Origin".parse().unwrap());
+        }
+    }
+    Ok(response)
+}
+
+/// The value of the Access-Control-Allow-Origin header for a request from `origin`, `None` if the origin is not
+/// allowed to read the response
+fn allowed_origin<'a>(origin: Option<&'a str>, allowed_origins: &[String]) -> Option<&'a str> {
+    if allowed_origins.iter().any(|allowed| allowed == "*") {
+        return Some("*");
+    }
+    let origin = origin?;
+    allowed_origins
+        .iter()
+        .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+        .then_some(origin)
+}
+
+async fn route(request: &Request<Body>, config: &RwLock<Config>, feed: &Feed) -> Response<Body> {
+    match request.uri().path() {
+        "/fissures" => match feed.fissures() {
+            Some(fissures) => json_response(&fissures),
+            None => not_fetched(),
+        },
+        "/fissures/filtered" => {
+            let profile = query_param(request, "profile");
+            filtered_fissures(&*config.read().await, feed, profile.as_deref())
+        }
+        "/config" => json_response(&config_json(&*config.read().await)),
+        "/events" => event_stream(feed),
+        "/health" => {
+            let relay = match feed.relay.get() {
+                Some(relay) => relay.health(),
+                None => Value::Null,
+            };
+            json_response(&json!({
+                "fissures_fetched": feed.fissures().is_some(),
+                "relay": relay,
+            }))
+        }
+        path => match (feed.relay.get(), relayed_platform(path)) {
+            (Some(relay), Some(platform)) => relay.fissures(platform, request).await,
+            _ => error(StatusCode::NOT_FOUND, &format!("No endpoint at {}", path)),
+        },
+    }
+}
+
+/// The fissures matching the filters of the given profile, or of any profile
+fn filtered_fissures(config: &Config, feed: &Feed, profile: Option<&str>) -> Response<Body> {
+    let profile = match profile {
+        Some(name) => match config.find_profile(name) {
+            Some(profile) => Some(profile),
+            None => {
+                return error(
+                    StatusCode::NOT_FOUND,
+                    &format!("Unknown profile \"{}\"", name),
+                )
+            }
+        },
+        None => None,
+    };
+    let Some(mut fissures) = feed.fissures() else {
+        return not_fetched();
+    };
+    fissures.retain(|fissure| match profile {
+        Some(profile) => {
+            !config.ignored_nodes.contains(&fissure.node) && profile.filter.matches(fissure)
+        }
+        None => config.matches(fissure),
+    });
+    json_response(&fissures)
+}
+
+/// The settings that are safe to share, leaving out webhook URLs, credentials and commands
+fn config_json(config: &Config) -> Value {
+    let profiles = config
+        .profiles()
+        .map(|profile| {
+            json!({
+                "name": profile.name,
+                "mission_filter": profile.filter.mission_filter,
+                "tier_filter": profile.filter.tier_filter,
+                "faction_filter": profile.filter.faction_filter,
+                "void_storm_filter": profile.filter.void_storm_filter,
+                "urgent": profile.urgent,
+                "urgency": profile.urgency,
+            })
+        })
+        .collect::<Vec<Value>>();
+    json!({
+        "profiles": profiles,
+        "refresh_rate": config.refresh_rate,
+        "adaptive_refresh": config.adaptive_refresh,
+        "min_refresh_rate": config.min_refresh_rate,
+        "max_refresh_rate": config.max_refresh_rate,
+        "expiry_window": config.expiry_window,
+        "time_before_expiry_notification": config.time_before_expiry_notification,
+        "announce_on_startup": config.announce_on_startup,
+        "notification_sinks": config.notification_sinks,
+        "ignored_nodes": config.ignored_nodes,
+        "snooze_minutes": config.snooze_minutes,
+    })
+}
+
+/// Streams the watcher's events as Server-Sent Events until the client disconnects
+fn event_stream(feed: &Feed) -> Response<Body> {
+    let mut events = feed.events.subscribe();
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        let mut keep_alive = tokio::time::interval(KEEP_ALIVE);
+        loop {
+            let chunk = tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => format!(
+                        "event: {}\ndata: {}\n\n",
+                        event.event,
+                        serde_json::to_string(&event).unwrap_or_default()
+                    ),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        format!(": skipped {} events\n\n", skipped)
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+                _ = keep_alive.tick() => ": keep-alive\n\n".to_string(),
+            };
+            // fails once the client disconnected
+            if sender.send_data(chunk.into()).await.is_err() {
+                return;
+            }
+        }
+    });
+    response(StatusCode::OK, "text/event-stream", body)
+}
+
+/// The platform of a `/{platform}/fissures` path
+fn relayed_platform(path: &str) -> Option<&str> {
+    path.strip_prefix('/')?
+        .strip_suffix("/fissures")
+        .filter(|platform| !platform.contains('/'))
+}
+
+/// The value of the query parameter, percent-decoded
+fn query_param(request: &Request<Body>, name: &str) -> Option<String> {
+    let url = Url::parse(&format!("http://localhost/?{}", request.uri().query()?)).ok()?;
+    let value = url
+        .query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned());
+    value
+}
+
+pub fn response(status: StatusCode, content_type: &str, body: Body) -> Response<Body> {
+    let mut response = Response::new(body);
+    *response.status_mut() = status;
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, content_type.parse().unwrap());
+    headers.insert(CACHE_CONTROL, "no-cache".parse().unwrap());
+    response
+}
+
+fn json_response(value: &impl Serialize) -> Response<Body> {
+    match serde_json::to_string(value) {
+        Ok(json) => response(StatusCode::OK, "application/json", json.into()),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+    }
+}
+
+pub fn error(status: StatusCode, message: &str) -> Response<Body> {
+    let body = json!({ "error": message }).to_string();
+    response(status, "application/json", body.into())
+}
+
+fn not_fetched() -> Response<Body> {
+    error(
+        StatusCode::SERVICE_UNAVAILABLE,
+        "The fissures have not been fetched yet",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_only_configured_origins() {
+        let origin = Some("http://localhost:8080");
+        assert_eq!(allowed_origin(origin, &[]), None);
+        let allowed = ["https://overlay.example.com".to_string()];
+        assert_eq!(allowed_origin(origin, &allowed), None);
+        assert_eq!(allowed_origin(None, &allowed), None);
+        let allowed = ["http://localhost:8080/".to_string()];
+        assert_eq!(allowed_origin(origin, &allowed), origin);
+        assert_eq!(allowed_origin(None, &["*".to_string()]), Some("*"));
+    }
+}
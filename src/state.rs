@@ -0,0 +1,296 @@
+use std::{collections::HashMap, error::Error, path::Path};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::models::Fissure;
+
+pub const STATE_PATH: &str = "reapers-wf-state.toml";
+
+/// A fissure remembered in the state file, kept until it expires
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StateEntry {
+    id: String,
+    #[serde(with = "time::serde::iso8601")]
+    expiry: OffsetDateTime,
+}
+
+/// What the user decided about a fissure, kept until it expires
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mark {
+    pub expiry: OffsetDateTime,
+    /// The user ran or skipped the fissure, its reminder is cancelled
+    pub acknowledged: bool,
+    /// The fissure is hidden and never notified
+    pub ignored: bool,
+    /// The reminder is postponed until then
+    pub snoozed_until: Option<OffsetDateTime>,
+}
+
+impl Mark {
+    fn new(expiry: OffsetDateTime) -> Self {
+        Self {
+            expiry,
+            acknowledged: false,
+            ignored: false,
+            snoozed_until: None,
+        }
+    }
+
+    /// Whether no reminder should be sent for the fissure
+    pub fn silenced(&self) -> bool {
+        self.acknowledged || self.ignored
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.acknowledged && !self.ignored && self.snoozed_until.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MarkEntry {
+    id: String,
+    #[serde(with = "time::serde::iso8601")]
+    expiry: OffsetDateTime,
+    #[serde(default)]
+    acknowledged: bool,
+    #[serde(default)]
+    ignored: bool,
+    #[serde(
+        default,
+        with = "time::serde::iso8601::option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    snoozed_until: Option<OffsetDateTime>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct StateFile {
+    #[serde(default)]
+    seen: Vec<StateEntry>,
+    #[serde(default)]
+    marks: Vec<MarkEntry>,
+}
+
+/// State of the fissure watcher that is kept across restarts
+#[derive(Default)]
+pub struct State {
+    /// Expiry of each fissure that has already been announced, by id
+    seen: HashMap<String, OffsetDateTime>,
+    /// Marks of fissures by id
+    marks: HashMap<String, Mark>,
+    /// Whether the state changed since it was last loaded or saved
+    dirty: bool,
+}
+
+impl State {
+    /// Loads the state file, returning an empty state if it does not exist
+    pub async fn load() -> Result<State, Box<dyn Error>> {
+        Self::load_from(Path::new(STATE_PATH)).await
+    }
+
+    async fn load_from(path: &Path) -> Result<State, Box<dyn Error>> {
+        let text = match tokio::fs::read_to_string(path).await {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(State::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let file = toml::from_str::<StateFile>(&text)?;
+        Ok(State {
+            seen: file
+                .seen
+                .into_iter()
+                .map(|entry| (entry.id, entry.expiry))
+                .collect(),
+            marks: file
+                .marks
+                .into_iter()
+                .map(|entry| {
+                    let mark = Mark {
+                        expiry: entry.expiry,
+                        acknowledged: entry.acknowledged,
+                        ignored: entry.ignored,
+                        snoozed_until: entry.snoozed_until,
+                    };
+                    (entry.id, mark)
+                })
+                .collect(),
+            dirty: false,
+        })
+    }
+
+    /// Writes the state file if anything changed since it was last loaded or saved
+    pub async fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        self.save_to(Path::new(STATE_PATH)).await
+    }
+
+    async fn save_to(&mut self, path: &Path) -> Result<(), Box<dyn Error>> {
+        if !self.dirty {
+            return Ok(());
+        }
+        let mut seen = self
+            .seen
+            .iter()
+            .map(|(id, expiry)| StateEntry {
+                id: id.clone(),
+                expiry: *expiry,
+            })
+            .collect::<Vec<StateEntry>>();
+        seen.sort_by_key(|entry| entry.expiry);
+        let mut marks = self
+            .marks
+            .iter()
+            .map(|(id, mark)| MarkEntry {
+                id: id.clone(),
+                expiry: mark.expiry,
+                acknowledged: mark.acknowledged,
+                ignored: mark.ignored,
+                snoozed_until: mark.snoozed_until,
+            })
+            .collect::<Vec<MarkEntry>>();
+        marks.sort_by_key(|entry| entry.expiry);
+        let text = toml::to_string(&StateFile { seen, marks })?;
+        tokio::fs::write(path, text).await?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Whether the given fissure has already been announced
+    pub fn is_seen(&self, fissure: &Fissure) -> bool {
+        self.seen.contains_key(&fissure.id)
+    }
+
+    pub fn mark_seen(&mut self, fissure: &Fissure) {
+        if self.seen.insert(fissure.id.clone(), fissure.expiry) != Some(fissure.expiry) {
+            self.dirty = true;
+        }
+    }
+
+    /// Forgets which fissures have been announced, so that all of them are announced again
+    pub fn clear_seen(&mut self) {
+        self.dirty |= !self.seen.is_empty();
+        self.seen.clear();
+    }
+
+    pub fn marks(&self) -> &HashMap<String, Mark> {
+        &self.marks
+    }
+
+    pub fn mark(&self, id: &str) -> Option<&Mark> {
+        self.marks.get(id)
+    }
+
+    /// Changes the mark of the given fissure, forgetting it once nothing is marked anymore
+    pub fn update_mark(&mut self, fissure: &Fissure, update: impl FnOnce(&mut Mark)) {
+        let mut mark = self
+            .marks
+            .get(&fissure.id)
+            .copied()
+            .unwrap_or_else(|| Mark::new(fissure.expiry));
+        update(&mut mark);
+        if mark.is_empty() {
+            self.dirty |= self.marks.remove(&fissure.id).is_some();
+        } else if self.marks.insert(fissure.id.clone(), mark) != Some(mark) {
+            self.dirty = true;
+        }
+    }
+
+    /// Forgets all fissures that have expired
+    pub fn prune(&mut self, now: OffsetDateTime) {
+        let len = self.seen.len() + self.marks.len();
+        self.seen.retain(|_, expiry| *expiry > now);
+        self.marks.retain(|_, mark| mark.expiry > now);
+        self.dirty |= self.seen.len() + self.marks.len() != len;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-01-01 12:00 UTC
+    fn now() -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_704_110_400).unwrap()
+    }
+
+    fn fissure(id: &str, minutes_left: i64) -> Fissure {
+        Fissure::sample(
+            id,
+            "Hydron (Sedna)",
+            now() + time::Duration::minutes(minutes_left),
+        )
+    }
+
+    #[tokio::test]
+    async fn save_and_load_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("reapers-wf-state-{}.toml", std::process::id()));
+        let mut state = State::default();
+        state.mark_seen(&fissure("seen", 30));
+        state.update_mark(&fissure("marked", 60), |mark| {
+            mark.ignored = true;
+            mark.snoozed_until = Some(now());
+        });
+        state.save_to(&path).await.unwrap();
+
+        let loaded = State::load_from(&path).await;
+        tokio::fs::remove_file(&path).await.unwrap();
+        let loaded = loaded.unwrap();
+        assert!(loaded.is_seen(&fissure("seen", 30)));
+        assert!(!loaded.is_seen(&fissure("marked", 60)));
+        assert_eq!(loaded.marks(), state.marks());
+        assert!(!loaded.dirty);
+
+        // a missing file is an empty state
+        let missing = State::load_from(&path).await.unwrap();
+        assert!(missing.seen.is_empty() && missing.marks.is_empty());
+    }
+
+    #[test]
+    fn prune_forgets_expired_fissures() {
+        let mut state = State::default();
+        state.mark_seen(&fissure("expired", -1));
+        state.mark_seen(&fissure("open", 1));
+        state.update_mark(&fissure("expired mark", 0), |mark| mark.acknowledged = true);
+        state.update_mark(&fissure("open mark", 1), |mark| mark.acknowledged = true);
+        state.prune(now());
+        assert!(!state.is_seen(&fissure("expired", -1)));
+        assert!(state.is_seen(&fissure("open", 1)));
+        assert_eq!(
+            state.marks().keys().collect::<Vec<&String>>(),
+            ["open mark"]
+        );
+    }
+
+    #[tokio::test]
+    async fn only_changes_make_the_state_dirty() {
+        let path =
+            std::env::temp_dir().join(format!("reapers-wf-dirty-{}.toml", std::process::id()));
+        let mut state = State::default();
+        state.mark_seen(&fissure("a", 30));
+        assert!(state.dirty);
+        state.save_to(&path).await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(!state.dirty);
+
+        // nothing changes, so nothing is written
+        state.mark_seen(&fissure("a", 30));
+        state.update_mark(&fissure("a", 30), |_| {});
+        state.prune(now());
+        assert!(!state.dirty);
+        state.save_to(&path).await.unwrap();
+        assert!(!path.exists());
+
+        state.update_mark(&fissure("a", 30), |mark| mark.acknowledged = true);
+        assert!(state.dirty);
+        state.dirty = false;
+        state.update_mark(&fissure("a", 30), |mark| mark.acknowledged = false);
+        assert!(state.dirty, "removing the last mark is a change");
+        state.dirty = false;
+        state.prune(now() + time::Duration::HOUR);
+        assert!(state.dirty);
+        state.dirty = false;
+        state.clear_seen();
+        assert!(!state.dirty, "nothing was left to clear");
+    }
+}
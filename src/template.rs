@@ -0,0 +1,360 @@
+use std::{collections::HashMap, fmt::Display};
+
+use serde::Deserialize;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    models::Fissure,
+    notifications::NotificationEvent,
+    util::{format_duration, local_offset},
+};
+
+/// Names that can be used as `{placeholder}` in templates
+pub const PLACEHOLDERS: &[&str] = &[
+    "event",
+    "count",
+    "fissure",
+    "id",
+    "activation",
+    "expiry",
+    "start_string",
+    "active",
+    "node",
+    "expired",
+    "eta",
+    "mission_type",
+    "mission_key",
+    "tier",
+    "tier_num",
+    "enemy",
+    "enemy_key",
+    "is_storm",
+    "is_hard",
+    "remaining",
+    "seconds",
+    "local_expiry",
+];
+
+/// How the value of a placeholder is escaped, chosen with `{placeholder|filter}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escape {
+    /// `raw`, the value as is
+    Raw,
+    /// `url`, percent-encoded for use in a URL
+    Url,
+    /// `json`, escaped for use inside a JSON string, without the quotes
+    Json,
+}
+
+impl Escape {
+    fn apply(&self, value: &str) -> String {
+        match self {
+            Escape::Raw => value.to_string(),
+            Escape::Url => value
+                .bytes()
+                .map(|byte| match byte {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                        (byte as char).to_string()
+                    }
+                    byte => format!("%{:02X}", byte),
+                })
+                .collect(),
+            Escape::Json => {
+                let quoted = serde_json::to_string(value).unwrap_or_default();
+                quoted[1..quoted.len() - 1].to_string()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    /// The name and the escaping chosen in the template, if any
+    Placeholder(String, Option<Escape>),
+}
+
+/// A text with `{placeholder}`s that are replaced when rendering, `{{` and `}}` produce literal braces.
+/// `{placeholder|url}`, `{placeholder|json}` and `{placeholder|raw}` choose how the value is escaped.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    UnknownPlaceholder(String),
+    UnknownFilter(String),
+    Unclosed,
+    UnmatchedBrace,
+}
+impl Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TemplateError::UnknownPlaceholder(name) => write!(
+                f,
+                "Unknown placeholder {{{}}}, expected one of: {}",
+                name,
+                PLACEHOLDERS.join(", ")
+            ),
+            TemplateError::UnknownFilter(name) => {
+                write!(f, "Unknown filter |{}, expected url, json or raw", name)
+            }
+            TemplateError::Unclosed => write!(f, "Unclosed placeholder, use {{{{ for a literal {{"),
+            TemplateError::UnmatchedBrace => write!(f, "Unmatched }}, use }}}} for a literal }}"),
+        }
+    }
+}
+impl std::error::Error for TemplateError {}
+
+impl Template {
+    pub fn parse(text: &str) -> Result<Template, TemplateError> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(TemplateError::UnmatchedBrace),
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(TemplateError::Unclosed),
+                        }
+                    }
+                    let (name, filter) = match name.split_once('|') {
+                        Some((name, filter)) => (name, Some(filter.trim())),
+                        None => (name.as_str(), None),
+                    };
+                    let name = name.trim().to_string();
+                    if !PLACEHOLDERS.contains(&name.as_str()) {
+                        return Err(TemplateError::UnknownPlaceholder(name));
+                    }
+                    let escape = match filter {
+                        None => None,
+                        Some("raw") => Some(Escape::Raw),
+                        Some("url") => Some(Escape::Url),
+                        Some("json") => Some(Escape::Json),
+                        Some(filter) => {
+                            return Err(TemplateError::UnknownFilter(filter.to_string()))
+                        }
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Placeholder(name, escape));
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+
+    /// Replaces the placeholders with the given values, missing values are replaced by nothing
+    pub fn render(&self, values: &HashMap<&str, String>) -> String {
+        self.render_escaped(values, Escape::Raw)
+    }
+
+    /// Like `render`, escaping the values of placeholders without a filter with `escape`
+    pub fn render_escaped(&self, values: &HashMap<&str, String>, escape: Escape) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Literal(text) => text.clone(),
+                Part::Placeholder(name, filter) => {
+                    let value = values.get(name.as_str()).map_or("", |value| value);
+                    filter.unwrap_or(escape).apply(value)
+                }
+            })
+            .collect()
+    }
+}
+impl TryFrom<String> for Template {
+    type Error = TemplateError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Template::parse(&text)
+    }
+}
+
+/// The summary and body of the notifications about one kind of event
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct EventTemplate {
+    pub summary: Template,
+    /// Rendered for every fissure of the event, one per line
+    pub body: Template,
+}
+
+/// The text of the notifications, per kind of event
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct NotificationTemplates {
+    #[serde(default = "default_new")]
+    pub new: EventTemplate,
+    #[serde(default = "default_expiring")]
+    pub expiring: EventTemplate,
+}
+
+fn default_new() -> EventTemplate {
+    EventTemplate {
+        summary: Template::parse("New Fissures").unwrap(),
+        body: Template::parse("{fissure}").unwrap(),
+    }
+}
+fn default_expiring() -> EventTemplate {
+    EventTemplate {
+        summary: Template::parse("Fissure is Expiring In {seconds} Seconds").unwrap(),
+        body: Template::parse("{fissure}").unwrap(),
+    }
+}
+
+impl Default for NotificationTemplates {
+    fn default() -> Self {
+        Self {
+            new: default_new(),
+            expiring: default_expiring(),
+        }
+    }
+}
+
+impl NotificationTemplates {
+    /// Renders the summary and body of the notification about the event.
+    /// The summary uses the values of the first fissure of the event.
+    pub fn render(&self, event: &NotificationEvent, now: OffsetDateTime) -> (String, String) {
+        let template = match event {
+            NotificationEvent::NewFissures(_) => &self.new,
+            NotificationEvent::Expiring { .. } => &self.expiring,
+        };
+        let fissures = event.fissures();
+        let summary = fissures
+            .first()
+            .map(|fissure| template.summary.render(&values(event, fissure, now)))
+            .unwrap_or_default();
+        let body = fissures
+            .iter()
+            .map(|fissure| template.body.render(&values(event, fissure, now)))
+            .collect::<Vec<String>>()
+            .join("\n");
+        (summary, body)
+    }
+}
+
+/// The values of all placeholders for an event about the fissure
+pub fn values(
+    event: &NotificationEvent,
+    fissure: &Fissure,
+    now: OffsetDateTime,
+) -> HashMap<&'static str, String> {
+    let time_format = time::format_description::parse("[hour]:[minute]:[second]").unwrap();
+    let remaining = fissure.expiry - now;
+    let seconds = match event {
+        NotificationEvent::Expiring { seconds, .. } => *seconds as i64,
+        NotificationEvent::NewFissures(_) => remaining.whole_seconds().max(0),
+    };
+    HashMap::from([
+        ("event", event.kind().to_string()),
+        ("count", event.fissures().len().to_string()),
+        ("fissure", fissure.to_string()),
+        ("id", fissure.id.clone()),
+        (
+            "activation",
+            fissure.activation.format(&Rfc3339).unwrap_or_default(),
+        ),
+        (
+            "expiry",
+            fissure.expiry.format(&Rfc3339).unwrap_or_default(),
+        ),
+        ("start_string", fissure.start_string.clone()),
+        ("active", fissure.active.to_string()),
+        ("node", fissure.node.clone()),
+        ("expired", fissure.expired.to_string()),
+        ("eta", fissure.eta.clone()),
+        ("mission_type", fissure.mission_type.to_string()),
+        ("mission_key", fissure.mission_key.clone()),
+        ("tier", fissure.tier.to_string()),
+        ("tier_num", fissure.tier_num.to_string()),
+        ("enemy", fissure.enemy.to_string()),
+        ("enemy_key", fissure.enemy_key.to_string()),
+        ("is_storm", fissure.is_storm.to_string()),
+        ("is_hard", fissure.is_hard.to_string()),
+        (
+            "remaining",
+            format_duration(remaining.max(time::Duration::ZERO)),
+        ),
+        ("seconds", seconds.to_string()),
+        (
+            "local_expiry",
+            fissure
+                .expiry
+                .to_offset(local_offset())
+                .format(&time_format)
+                .unwrap_or_default(),
+        ),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> HashMap<&'static str, String> {
+        HashMap::from([
+            ("node", "Hydron (Sedna)".to_string()),
+            ("fissure", "Say \"hi\" & leave\n".to_string()),
+        ])
+    }
+
+    #[test]
+    fn renders_placeholders_and_literal_braces() {
+        let template = Template::parse("{{{node}}} {count}").unwrap();
+        assert_eq!(template.render(&values()), "{Hydron (Sedna)} ");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders_and_filters() {
+        assert_eq!(
+            Template::parse("{nope}"),
+            Err(TemplateError::UnknownPlaceholder("nope".to_string()))
+        );
+        assert_eq!(
+            Template::parse("{node|html}"),
+            Err(TemplateError::UnknownFilter("html".to_string()))
+        );
+        assert_eq!(Template::parse("{node"), Err(TemplateError::Unclosed));
+    }
+
+    #[test]
+    fn filters_escape_values() {
+        let template = Template::parse("{node|url} {fissure|json} {node | raw}").unwrap();
+        assert_eq!(
+            template.render(&values()),
+            "Hydron%20%28Sedna%29 Say \\\"hi\\\" & leave\\n Hydron (Sedna)"
+        );
+    }
+
+    #[test]
+    fn default_escape_applies_to_placeholders_without_filter() {
+        let template = Template::parse("https://example.com/?node={node}&raw={node|raw}").unwrap();
+        assert_eq!(
+            template.render_escaped(&values(), Escape::Url),
+            "https://example.com/?node=Hydron%20%28Sedna%29&raw=Hydron (Sedna)"
+        );
+        let template = Template::parse("{{\"text\": \"{fissure}\"}}").unwrap();
+        let body = template.render_escaped(&values(), Escape::Json);
+        let json = serde_json::from_str::<serde_json::Value>(&body).unwrap();
+        assert_eq!(json["text"], "Say \"hi\" & leave\n");
+    }
+}
@@ -0,0 +1,139 @@
+//! Helpers shared by the unit tests
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream},
+};
+
+/// A request received by the `MockServer`
+#[derive(Debug, Clone)]
+pub struct Recorded {
+    pub method: Method,
+    /// Path and query
+    pub uri: String,
+    pub body: String,
+}
+
+impl Recorded {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("the request body is not JSON")
+    }
+}
+
+/// An HTTP server on a free local port that records the requests it receives
+pub struct MockServer {
+    pub addr: SocketAddr,
+    requests: Arc<Mutex<Vec<Recorded>>>,
+}
+
+impl MockServer {
+    /// Starts the server, answering each request with the status and body returned by `respond`
+    pub fn start(respond: fn(&Recorded) -> (StatusCode, String)) -> Self {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+        let make_service = make_service_fn(move |_| {
+            let recorded = Arc::clone(&recorded);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let recorded = Arc::clone(&recorded);
+                    async move {
+                        let (parts, body) = request.into_parts();
+                        let body = hyper::body::to_bytes(body).await.unwrap_or_default();
+                        let request = Recorded {
+                            method: parts.method,
+                            uri: parts.uri.to_string(),
+                            body: String::from_utf8_lossy(&body).into_owned(),
+                        };
+                        let (status, body) = respond(&request);
+                        recorded.lock().unwrap().push(request);
+                        let mut response = Response::new(Body::from(body));
+                        *response.status_mut() = status;
+                        Ok::<_, Infallible>(response)
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        Self { addr, requests }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{}", self.addr, path)
+    }
+
+    /// The requests received so far, in order
+    pub fn requests(&self) -> Vec<Recorded> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// An SMTP server on a free local port that accepts every message, recording the data of each
+pub struct MockSmtpServer {
+    pub port: u16,
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl MockSmtpServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+            .await
+            .unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&messages);
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(smtp_session(stream, Arc::clone(&recorded)));
+            }
+        });
+        Self { port, messages }
+    }
+
+    /// The headers and body of the messages received so far, in order
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+/// Answers the commands of one client until it quits
+async fn smtp_session(stream: TcpStream, messages: Arc<Mutex<Vec<String>>>) -> std::io::Result<()> {
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    write.write_all(b"220 localhost ESMTP\r\n").await?;
+    while let Some(line) = lines.next_line().await? {
+        let command = line.to_uppercase();
+        let reply: &[u8] = if command.starts_with("DATA") {
+            write
+                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                .await?;
+            let mut data = Vec::new();
+            while let Some(line) = lines.next_line().await? {
+                if line == "." {
+                    break;
+                }
+                data.push(line);
+            }
+            messages.lock().unwrap().push(data.join("\n"));
+            b"250 OK\r\n"
+        } else if command.starts_with("QUIT") {
+            write.write_all(b"221 Bye\r\n").await?;
+            return Ok(());
+        } else {
+            b"250 OK\r\n"
+        };
+        write.write_all(reply).await?;
+    }
+    Ok(())
+}
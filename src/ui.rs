@@ -1,6 +1,10 @@
 use ratatui::{prelude::*, widgets::*};
 
-use crate::{app::App, models::Fissure};
+use crate::{
+    app::{App, Banner},
+    history::GroupStatistics,
+    util::format_duration,
+};
 
 pub fn draw(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
@@ -16,17 +20,67 @@ pub fn draw(f: &mut Frame, app: &mut App) {
         .iter()
         .map(|t| text::Line::from(Span::styled(*t, Style::default().fg(Color::Red))))
         .collect();
+    // the "Terminal" sink flashes the tab bar by alternating its border colour
+    let flashing = app.flash_until.is_some_and(|until| {
+        until
+            .saturating_duration_since(std::time::Instant::now())
+            .as_millis()
+            / 250
+            % 2
+            == 1
+    });
+    let tab_block = match flashing {
+        true => Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow).bold()),
+        false => Block::default().borders(Borders::ALL),
+    };
     let tabs = Tabs::new(titles)
-        .block(Block::default().borders(Borders::ALL))
+        .block(tab_block)
         .highlight_style(Style::default().fg(Color::LightRed).bold())
         .select(app.tabs.index);
     f.render_widget(tabs, chunks[0]);
-    draw_footer(f, chunks[2]);
+    draw_footer(f, app, chunks[2]);
     match app.tabs.index {
         0 => draw_console_tab(f, app, chunks[1]),
         1 => app.fissure_watcher.draw(f, chunks[1]),
+        2 => draw_statistics_tab(f, app, chunks[1]),
+        3 => app.inbox.draw(f, chunks[1]),
         _ => {}
     };
+    if let Some(banner) = &app.banner {
+        draw_banner(f, banner, chunks[1]);
+    }
+}
+
+/// Draws the banner of the "Terminal" sink at the top of the area, over the current tab
+fn draw_banner(f: &mut Frame, banner: &Banner, area: Rect) {
+    let longest = banner
+        .lines
+        .iter()
+        .chain(std::iter::once(&banner.summary))
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or_default();
+    let width = (longest as u16 + 4).min(area.width);
+    let height = (banner.lines.len() as u16 + 2).min(area.height);
+    let banner_area = Rect::new(area.x + (area.width - width) / 2, area.y, width, height);
+    let lines = banner
+        .lines
+        .iter()
+        .map(|line| text::Line::from(line.as_str()))
+        .collect::<Vec<text::Line>>();
+    let paragraph = Paragraph::new(lines).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow))
+            .title(Span::styled(
+                banner.summary.as_str(),
+                Style::default().bold(),
+            )),
+    );
+    f.render_widget(Clear, banner_area);
+    f.render_widget(paragraph, banner_area);
 }
 
 fn draw_console_tab(f: &mut Frame, app: &mut App, area: Rect) {
@@ -45,18 +99,131 @@ fn draw_console_tab(f: &mut Frame, app: &mut App, area: Rect) {
     )
     .highlight_style(Style::default().bold());
     f.render_stateful_widget(log, chunks[0], &mut app.console_log.state);
-    let current_command = Paragraph::new(Line::from(vec![
-        format!("> {}", app.current_cmd.clone()).into(),
-        "█".into(),
-    ]));
+    // scroll the prompt horizontally so that the cursor always stays visible
+    const PROMPT: &str = "> ";
+    let width = chunks[1].width.saturating_sub(PROMPT.len() as u16 + 1) as usize;
+    let cursor = app.current_cmd.cursor();
+    let scroll = cursor.saturating_sub(width);
+    let visible = app
+        .current_cmd
+        .text()
+        .chars()
+        .skip(scroll)
+        .take(width)
+        .collect::<String>();
+    let current_command = Paragraph::new(format!("{}{}", PROMPT, visible));
     f.render_widget(current_command, chunks[1]);
+    f.set_cursor(
+        chunks[1].x + (PROMPT.len() + cursor - scroll) as u16,
+        chunks[1].y,
+    );
+}
+
+fn draw_statistics_tab(f: &mut Frame, app: &App, area: Rect) {
+    let statistics = &app.fissure_watcher.statistics;
+    let chunks = Layout::default()
+        .constraints([
+            Constraint::Length(2), // summary
+            Constraint::Min(0),    // tables
+        ])
+        .split(area);
+    let date_format: Vec<time::format_description::FormatItem<'_>> =
+        time::format_description::parse("[year]-[month]-[day] [hour]:[minute]").unwrap();
+    let summary = match statistics.since {
+        Some(since) => format!(
+            "{} fissures recorded since {} UTC",
+            statistics.total,
+            since.format(&date_format).unwrap()
+        ),
+        None => "No fissures recorded yet".to_string(),
+    };
+    f.render_widget(Paragraph::new(summary), chunks[0]);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage(25), // tiers
+            Constraint::Percentage(30), // missions
+            Constraint::Percentage(45), // filter matches
+        ])
+        .split(chunks[1]);
+    let group_rows = |groups: &[GroupStatistics]| {
+        groups
+            .iter()
+            .map(|group| {
+                vec![
+                    group.name.clone(),
+                    group.count.to_string(),
+                    format_duration(group.average_duration),
+                ]
+            })
+            .collect::<Vec<Vec<String>>>()
+    };
+    draw_table(
+        f,
+        columns[0],
+        "Per tier",
+        vec![
+            "Tier".to_string(),
+            "Seen".to_string(),
+            "Avg duration".to_string(),
+        ],
+        group_rows(&statistics.tiers),
+    );
+    draw_table(
+        f,
+        columns[1],
+        "Per mission",
+        vec![
+            "Mission".to_string(),
+            "Seen".to_string(),
+            "Avg duration".to_string(),
+        ],
+        group_rows(&statistics.missions),
+    );
+    let now = time::OffsetDateTime::now_utc();
+    let match_rows = statistics
+        .matches
+        .iter()
+        .map(|gap| {
+            vec![
+                gap.name.clone(),
+                gap.count.to_string(),
+                gap.average_gap.map(format_duration).unwrap_or_default(),
+                gap.longest_gap.map(format_duration).unwrap_or_default(),
+                format!("{} ago", format_duration(now - gap.last_seen)),
+            ]
+        })
+        .collect::<Vec<Vec<String>>>();
+    draw_table(
+        f,
+        columns[2],
+        "Matching your filters",
+        vec![
+            "Fissure".to_string(),
+            "Seen".to_string(),
+            "Avg gap".to_string(),
+            "Longest gap".to_string(),
+            "Last seen".to_string(),
+        ],
+        match_rows,
+    );
 }
 
+/// Draws a titled table with columns sized to fit their contents
+fn draw_table(f: &mut Frame, area: Rect, title: &str, header: Vec<String>, rows: Vec<Vec<String>>) {
+    let widths = calculate_table_widths(&header, &rows);
+    let table = Table::new(rows.into_iter().map(Row::new))
+        .header(Row::new(header).bold())
+        .widths(&widths)
+        .column_spacing(2)
+        .block(Block::default().borders(Borders::TOP).title(title));
+    f.render_widget(table, area);
+}
 
 /// Calculate the widths of the table columns based on the longest string in each column.
 /// # Returns
 /// A vector of `Constraint::Length(max)`s with `max` being the longest number of chars in that column.
-pub fn calculate_table_widths(header: &Vec<String>, rows: &Vec<Vec<String>>) -> Vec<Constraint> {
+pub fn calculate_table_widths(header: &[String], rows: &[Vec<String>]) -> Vec<Constraint> {
     let mut widths = Vec::with_capacity(header.len());
     for (i, column) in header.iter().enumerate() {
         let mut max = column.chars().count();
@@ -71,10 +238,32 @@ pub fn calculate_table_widths(header: &Vec<String>, rows: &Vec<Vec<String>>) ->
     widths
 }
 
-fn draw_footer(f: &mut Frame, area: Rect) {
+fn draw_footer(f: &mut Frame, app: &App, area: Rect) {
     const BUILT_ON: &str = compile_time::datetime_str!();
+    let refresh = match app.fissure_watcher.next_refresh {
+        Some(next_refresh) => {
+            let time_format: Vec<time::format_description::FormatItem<'_>> =
+                time::format_description::parse("[hour]:[minute]:[second]").unwrap();
+            let seconds = (next_refresh - time::OffsetDateTime::now_utc())
+                .whole_seconds()
+                .max(0);
+            format!(
+                "Next {}refresh at {} UTC (in {}:{:02})",
+                if app.fissure_watcher.adaptive_refresh {
+                    "adaptive "
+                } else {
+                    ""
+                },
+                next_refresh.format(&time_format).unwrap_or_default(),
+                seconds / 60,
+                seconds % 60
+            )
+        }
+        None => "Refresh paused".to_string(),
+    };
     let text = format!(
-        "Press ESC To Exit | Reaper's Warframe Tools v{} (compiled {} UTC)",
+        "Press ESC To Exit | {} | Reaper's Warframe Tools v{} (compiled {} UTC)",
+        refresh,
         env!("CARGO_PKG_VERSION"),
         BUILT_ON.replace('T', " ").replace('Z', "")
     );
@@ -1,5 +1,19 @@
 #![allow(dead_code)]
-use std::fmt::Display;
+use std::{fmt::Display, sync::OnceLock};
+
+use time::UtcOffset;
+
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
+/// Determines the local UTC offset, which is only possible while the process is single threaded
+pub fn init_local_offset() {
+    let _ = LOCAL_OFFSET.set(UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC));
+}
+
+/// The local UTC offset determined at startup, UTC if it could not be determined
+pub fn local_offset() -> UtcOffset {
+    LOCAL_OFFSET.get().copied().unwrap_or(UtcOffset::UTC)
+}
 
 /// Inserts a space before each capital letter except the first
 pub fn separate_camel_case(string: &str) -> String {
@@ -32,6 +46,22 @@ pub fn table(data: Vec<Vec<String>>) -> String {
     table
 }
 
-pub fn comma_separated_string<T>(input: &Vec<T>) -> String where T: Display {
+/// Formats a duration as a short human readable string, such as "2d 3h", "1h 5m" or "42s"
+pub fn format_duration(duration: time::Duration) -> String {
+    let seconds = duration.whole_seconds().abs();
+    let sign = if duration.is_negative() { "-" } else { "" };
+    let (days, hours, minutes) = (seconds / 86400, seconds % 86400 / 3600, seconds % 3600 / 60);
+    if days > 0 {
+        format!("{}{}d {}h", sign, days, hours)
+    } else if hours > 0 {
+        format!("{}{}h {}m", sign, hours, minutes)
+    } else if minutes > 0 {
+        format!("{}{}m {}s", sign, minutes, seconds % 60)
+    } else {
+        format!("{}{}s", sign, seconds)
+    }
+}
+
+pub fn comma_separated_string<T>(input: &[T]) -> String where T: Display {
     input.iter().map(|tier| tier.to_string()).collect::<Vec<String>>().join(", ")
 }